@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use serde::{Deserialize, Serialize};
+use serde_shade_nbt::{from_slice, to_vec, to_writer};
+
+#[derive(Serialize, Deserialize)]
+struct Position {
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Item {
+    id: String,
+    count: u8,
+    damage: i16,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Player {
+    name: String,
+    uuid: u128,
+    pos: Position,
+    health: f32,
+    xp: i32,
+    inventory: Vec<Item>,
+    flags: BTreeMap<String, bool>,
+}
+
+fn player() -> Player {
+    Player {
+        name: "Steve".to_string(),
+        uuid: 0x0123_4567_89ab_cdef_0123_4567_89ab_cdef,
+        pos: Position {
+            x: 12.5,
+            y: 64.0,
+            z: -3.25,
+        },
+        health: 20.0,
+        xp: 1337,
+        inventory: (0..36)
+            .map(|i| Item {
+                id: format!("minecraft:item_{i}"),
+                count: i as u8 + 1,
+                damage: i * 3,
+            })
+            .collect(),
+        flags: ["flying", "sneaking", "sprinting", "swimming"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, flag)| (flag.to_string(), i % 2 == 0))
+            .collect(),
+    }
+}
+
+fn player_benches(c: &mut Criterion) {
+    let player = player();
+    let bytes = to_vec(&player).unwrap();
+    c.bench_function("serialize player", |b| {
+        b.iter(|| to_vec(black_box(&player)).unwrap())
+    });
+    c.bench_function("serialize player to writer", |b| {
+        b.iter_batched_ref(
+            || Vec::with_capacity(bytes.len()),
+            |out| to_writer(out, black_box(&player)).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("deserialize player", |b| {
+        b.iter(|| from_slice::<Player>(black_box(&bytes)).unwrap())
+    });
+}
+
+fn list_benches(c: &mut Criterion) {
+    let numbers: Vec<i32> = (0..100_000).collect();
+    let bytes = to_vec(&numbers).unwrap();
+    c.bench_function("serialize 100k ints", |b| {
+        b.iter(|| to_vec(black_box(&numbers)).unwrap())
+    });
+    c.bench_function("deserialize 100k ints", |b| {
+        b.iter(|| from_slice::<Vec<i32>>(black_box(&bytes)).unwrap())
+    });
+
+    let names: Vec<String> = (0..10_000).map(|i| format!("entity\u{0}{i}")).collect();
+    let bytes = to_vec(&names).unwrap();
+    c.bench_function("serialize 10k strings with nulls", |b| {
+        b.iter(|| to_vec(black_box(&names)).unwrap())
+    });
+    c.bench_function("deserialize 10k strings with nulls", |b| {
+        b.iter(|| from_slice::<Vec<String>>(black_box(&bytes)).unwrap())
+    });
+}
+
+#[cfg(feature = "arena")]
+fn arena_benches(c: &mut Criterion) {
+    use serde_shade_nbt::Value;
+
+    let entities: BTreeMap<String, Player> = (0..1000)
+        .map(|i| (format!("player{i}"), player()))
+        .collect();
+    let bytes = to_vec(&entities).unwrap();
+    c.bench_function("deserialize 1k players into Value", |b| {
+        b.iter(|| from_slice::<Value>(black_box(&bytes)).unwrap())
+    });
+    c.bench_function("deserialize 1k players into an arena", |b| {
+        let mut arena = bumpalo::Bump::new();
+        b.iter(|| {
+            Value::from_slice_in(black_box(&bytes), &arena).unwrap();
+            arena.reset();
+        })
+    });
+}
+
+#[cfg(not(feature = "arena"))]
+criterion_group!(benches, player_benches, list_benches);
+#[cfg(feature = "arena")]
+criterion_group!(benches, player_benches, list_benches, arena_benches);
+criterion_main!(benches);
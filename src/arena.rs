@@ -0,0 +1,198 @@
+use std::fmt;
+
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+
+use crate::de::Deserializer;
+use crate::{Tag, Value};
+
+/// A [`Value`] whose nodes live in a [`Bump`] arena instead of individual heap allocations.
+///
+/// Strings and byte arrays borrow from the input where the encoding allows it and are copied
+/// into the arena otherwise. Compound fields are kept in document order; look them up with
+/// [`get`](ArenaValue::get).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArenaValue<'a> {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(&'a [u8]),
+    String(&'a str),
+    List(&'a [ArenaValue<'a>]),
+    Compound(&'a [(&'a str, ArenaValue<'a>)]),
+}
+
+impl<'a> ArenaValue<'a> {
+    pub fn tag(&self) -> Tag {
+        match self {
+            ArenaValue::Byte(_) => Tag::Byte,
+            ArenaValue::Short(_) => Tag::Short,
+            ArenaValue::Int(_) => Tag::Int,
+            ArenaValue::Long(_) => Tag::Long,
+            ArenaValue::Float(_) => Tag::Float,
+            ArenaValue::Double(_) => Tag::Double,
+            ArenaValue::ByteArray(_) => Tag::ByteArray,
+            ArenaValue::String(_) => Tag::String,
+            ArenaValue::List(_) => Tag::List,
+            ArenaValue::Compound(_) => Tag::Compound,
+        }
+    }
+
+    /// Returns the field called `key` if this is a compound that has one.
+    ///
+    /// This is a linear scan; if a document repeats a name, the last occurrence wins, as it does
+    /// when decoding into a [`Value`].
+    pub fn get(&self, key: &str) -> Option<&ArenaValue<'a>> {
+        match self {
+            ArenaValue::Compound(fields) => {
+                fields.iter().rev().find(|(k, _)| *k == key).map(|(_, v)| v)
+            }
+            _ => None,
+        }
+    }
+
+    /// Copies the value out of the arena.
+    pub fn to_value(&self) -> Value {
+        match *self {
+            ArenaValue::Byte(v) => Value::Byte(v),
+            ArenaValue::Short(v) => Value::Short(v),
+            ArenaValue::Int(v) => Value::Int(v),
+            ArenaValue::Long(v) => Value::Long(v),
+            ArenaValue::Float(v) => Value::Float(v),
+            ArenaValue::Double(v) => Value::Double(v),
+            ArenaValue::ByteArray(v) => Value::ByteArray(v.to_vec()),
+            ArenaValue::String(v) => Value::String(v.to_owned()),
+            ArenaValue::List(v) => Value::List(v.iter().map(ArenaValue::to_value).collect()),
+            ArenaValue::Compound(v) => Value::Compound(
+                v.iter()
+                    .map(|(k, v)| ((*k).to_owned(), v.to_value()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Value {
+    /// Decodes a document into an [`ArenaValue`] allocated in `arena`.
+    ///
+    /// The result borrows both `input` and `arena`, so neither can be dropped or reset while it
+    /// is in use. Dropping the arena frees the whole tree at once.
+    pub fn from_slice_in<'a>(input: &'a [u8], arena: &'a Bump) -> crate::Result<ArenaValue<'a>> {
+        let mut de = Deserializer::from_slice(input)?;
+        ArenaSeed(arena).deserialize(&mut de)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ArenaSeed<'a>(&'a Bump);
+
+impl<'a> DeserializeSeed<'a> for ArenaSeed<'a> {
+    type Value = ArenaValue<'a>;
+
+    fn deserialize<D: de::Deserializer<'a>>(
+        self,
+        deserializer: D,
+    ) -> Result<ArenaValue<'a>, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'a> Visitor<'a> for ArenaSeed<'a> {
+    type Value = ArenaValue<'a>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a ShadeNBT value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<ArenaValue<'a>, E> {
+        Ok(ArenaValue::Byte(v.into()))
+    }
+
+    fn visit_i8<E>(self, v: i8) -> Result<ArenaValue<'a>, E> {
+        Ok(ArenaValue::Byte(v))
+    }
+
+    fn visit_i16<E>(self, v: i16) -> Result<ArenaValue<'a>, E> {
+        Ok(ArenaValue::Short(v))
+    }
+
+    fn visit_i32<E>(self, v: i32) -> Result<ArenaValue<'a>, E> {
+        Ok(ArenaValue::Int(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<ArenaValue<'a>, E> {
+        Ok(ArenaValue::Long(v))
+    }
+
+    fn visit_f32<E>(self, v: f32) -> Result<ArenaValue<'a>, E> {
+        Ok(ArenaValue::Float(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<ArenaValue<'a>, E> {
+        Ok(ArenaValue::Double(v))
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'a str) -> Result<ArenaValue<'a>, E> {
+        Ok(ArenaValue::String(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<ArenaValue<'a>, E> {
+        Ok(ArenaValue::String(self.0.alloc_str(v)))
+    }
+
+    fn visit_borrowed_bytes<E>(self, v: &'a [u8]) -> Result<ArenaValue<'a>, E> {
+        Ok(ArenaValue::ByteArray(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<ArenaValue<'a>, E> {
+        Ok(ArenaValue::ByteArray(self.0.alloc_slice_copy(v)))
+    }
+
+    fn visit_seq<A: SeqAccess<'a>>(self, mut seq: A) -> Result<ArenaValue<'a>, A::Error> {
+        let capacity = seq.size_hint().unwrap_or(0).min(4096);
+        let mut list = BumpVec::with_capacity_in(capacity, self.0);
+        while let Some(element) = seq.next_element_seed(self)? {
+            list.push(element);
+        }
+        Ok(ArenaValue::List(list.into_bump_slice()))
+    }
+
+    fn visit_map<A: MapAccess<'a>>(self, mut map: A) -> Result<ArenaValue<'a>, A::Error> {
+        let mut fields = BumpVec::new_in(self.0);
+        while let Some(key) = map.next_key_seed(NameSeed(self.0))? {
+            fields.push((key, map.next_value_seed(self)?));
+        }
+        Ok(ArenaValue::Compound(fields.into_bump_slice()))
+    }
+}
+
+/// Reads a field name, copying it into the arena only if it can't be borrowed.
+struct NameSeed<'a>(&'a Bump);
+
+impl<'a> DeserializeSeed<'a> for NameSeed<'a> {
+    type Value = &'a str;
+
+    fn deserialize<D: de::Deserializer<'a>>(self, deserializer: D) -> Result<&'a str, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'a> Visitor<'a> for NameSeed<'a> {
+    type Value = &'a str;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a field name")
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'a str) -> Result<&'a str, E> {
+        Ok(v)
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<&'a str, E> {
+        Ok(self.0.alloc_str(v))
+    }
+}
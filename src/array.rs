@@ -0,0 +1,126 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserialize, SeqAccess, Visitor};
+use serde::ser::Serialize;
+
+use crate::Tag;
+
+/// Newtype names that tell the ShadeNBT serializer and deserializer to use an array tag. Other
+/// formats see an ordinary newtype around a sequence.
+pub(crate) const INT_ARRAY: &str = "$serde_shade_nbt::IntArray";
+pub(crate) const LONG_ARRAY: &str = "$serde_shade_nbt::LongArray";
+
+/// Returns the array tag and element tag for one of the array newtype names.
+pub(crate) fn array_tags(name: &str) -> Option<(Tag, Tag)> {
+    match name {
+        INT_ARRAY => Some((Tag::IntArray, Tag::Int)),
+        LONG_ARRAY => Some((Tag::LongArray, Tag::Long)),
+        _ => None,
+    }
+}
+
+/// A list of `i32`s stored with [`Tag::IntArray`], which packs the elements without per-element
+/// tags and is decoded in a single pass.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct IntArray(pub Vec<i32>);
+
+/// A list of `i64`s stored with [`Tag::LongArray`], which packs the elements without
+/// per-element tags and is decoded in a single pass.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LongArray(pub Vec<i64>);
+
+impl Serialize for IntArray {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(INT_ARRAY, &self.0)
+    }
+}
+
+impl Serialize for LongArray {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(LONG_ARRAY, &self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for IntArray {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_array(deserializer, INT_ARRAY).map(IntArray)
+    }
+}
+
+impl<'de> Deserialize<'de> for LongArray {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_array(deserializer, LONG_ARRAY).map(LongArray)
+    }
+}
+
+/// Decodes the array stored under one of the array newtype names, as written by serializing
+/// the elements in a newtype struct with that name.
+pub(crate) fn deserialize_array<'de, D, T>(
+    deserializer: D,
+    name: &'static str,
+) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Element + Deserialize<'de>,
+{
+    deserializer.deserialize_newtype_struct(name, ArrayVisitor(PhantomData))
+}
+
+/// An array element that can be decoded from its little-endian bytes.
+pub(crate) trait Element: Sized {
+    const WIDTH: usize;
+
+    fn from_le_bytes(bytes: &[u8]) -> Self;
+}
+
+impl Element for i32 {
+    const WIDTH: usize = 4;
+
+    fn from_le_bytes(bytes: &[u8]) -> Self {
+        i32::from_le_bytes(bytes.try_into().expect("chunk has the element width"))
+    }
+}
+
+impl Element for i64 {
+    const WIDTH: usize = 8;
+
+    fn from_le_bytes(bytes: &[u8]) -> Self {
+        i64::from_le_bytes(bytes.try_into().expect("chunk has the element width"))
+    }
+}
+
+/// Accepts the whole array as little-endian bytes from the ShadeNBT deserializer, or a newtype
+/// around a sequence from any other format.
+struct ArrayVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Element + Deserialize<'de>> Visitor<'de> for ArrayVisitor<T> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an array of {} byte integers", T::WIDTH)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<T>, E> {
+        let words = v.chunks_exact(T::WIDTH);
+        if !words.remainder().is_empty() {
+            return Err(E::invalid_length(v.len(), &self));
+        }
+        Ok(words.map(T::from_le_bytes).collect())
+    }
+
+    fn visit_newtype_struct<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Vec<T>, D::Error> {
+        Vec::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<T>, A::Error> {
+        let mut array = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(element) = seq.next_element()? {
+            array.push(element);
+        }
+        Ok(array)
+    }
+}
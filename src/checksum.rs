@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::{from_slice, to_vec};
+
+/// The length of the trailer appended by [`to_vec_checked`]: the document length and its
+/// CRC-32, both as little-endian `u32`s.
+const TRAILER_LEN: usize = 8;
+
+/// Serializes `value` like [`to_vec`], then appends a trailer holding the document's length and
+/// CRC-32 so that [`from_slice_checked`] can detect truncation and corruption.
+///
+/// The output is not a plain ShadeNBT document; readers that don't know about the trailer see
+/// eight unexpected bytes after the root terminator. Documents longer than `u32::MAX` bytes
+/// fail with [`Error::DocumentTooLong`].
+pub fn to_vec_checked<T: ?Sized + Serialize>(value: &T) -> Result<Vec<u8>> {
+    let mut buf = to_vec(value)?;
+    let len = u32::try_from(buf.len()).map_err(|_| Error::DocumentTooLong(buf.len()))?;
+    let crc = crc32(&buf);
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(&crc.to_le_bytes());
+    Ok(buf)
+}
+
+/// Checks the trailer written by [`to_vec_checked`] and decodes the document in front of it.
+///
+/// Fails with [`Error::MissingTrailer`] if the recorded length doesn't match, which is also how
+/// a plain document without a trailer is reported, and with [`Error::ChecksumMismatch`] if the
+/// bytes have changed.
+pub fn from_slice_checked<'de, T: Deserialize<'de>>(input: &'de [u8]) -> Result<T> {
+    let split = input
+        .len()
+        .checked_sub(TRAILER_LEN)
+        .ok_or(Error::MissingTrailer)?;
+    let (document, trailer) = input.split_at(split);
+    let len = u32::from_le_bytes(trailer[..4].try_into().expect("the slice is 4 bytes long"));
+    if usize::try_from(len).ok() != Some(document.len()) {
+        Err(Error::MissingTrailer)?
+    }
+    let expected = u32::from_le_bytes(trailer[4..].try_into().expect("the slice is 4 bytes long"));
+    let found = crc32(document);
+    if found != expected {
+        Err(Error::ChecksumMismatch(expected, found))?
+    }
+    from_slice(document)
+}
+
+/// The CRC-32 used by zlib and PNG (reflected, polynomial `0xEDB88320`).
+fn crc32(bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    !bytes.iter().fold(!0, |crc, &byte| {
+        TABLE[usize::from(crc as u8 ^ byte)] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::crc32;
+
+    #[test]
+    fn check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
@@ -0,0 +1,480 @@
+//! Adapters for `#[serde(with = "...")]` that store plain Rust collections with a dedicated
+//! ShadeNBT tag.
+
+/// Stores a `Vec<i8>` as a [`Tag::ByteArray`](crate::Tag::ByteArray) rather than a list of
+/// bytes. Each byte holds the two's complement of its element, so negative values survive the
+/// round trip.
+pub mod i8_array {
+    use std::fmt;
+
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &[i8], serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes: Vec<u8> = value.iter().map(|&b| b as u8).collect();
+        serializer.serialize_bytes(&bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<i8>, D::Error> {
+        deserializer.deserialize_byte_buf(I8ArrayVisitor)
+    }
+
+    struct I8ArrayVisitor;
+
+    impl<'de> Visitor<'de> for I8ArrayVisitor {
+        type Value = Vec<i8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a byte array")
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<i8>, E> {
+            Ok(v.iter().map(|&b| b as i8).collect())
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<i8>, A::Error> {
+            let mut array = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(element) = seq.next_element()? {
+                array.push(element);
+            }
+            Ok(array)
+        }
+    }
+}
+
+/// Packs a `Vec<bool>` into a [`Tag::ByteArray`](crate::Tag::ByteArray) holding the element
+/// count as a little-endian `u32` followed by the bits, eight to a byte with the first element in
+/// the lowest bit. Unused bits of the last byte are written as zero and ignored when reading.
+pub mod bitset {
+    use std::fmt;
+
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &[bool], serializer: S) -> Result<S::Ok, S::Error> {
+        let len = u32::try_from(value.len()).map_err(serde::ser::Error::custom)?;
+        let mut bytes = Vec::with_capacity(4 + value.len().div_ceil(8));
+        bytes.extend_from_slice(&len.to_le_bytes());
+        for chunk in value.chunks(8) {
+            let byte = chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (bit, &set)| byte | (u8::from(set) << bit));
+            bytes.push(byte);
+        }
+        serializer.serialize_bytes(&bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<bool>, D::Error> {
+        deserializer.deserialize_byte_buf(BitsetVisitor)
+    }
+
+    struct BitsetVisitor;
+
+    impl<'de> Visitor<'de> for BitsetVisitor {
+        type Value = Vec<bool>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a bit count followed by the packed bits")
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<bool>, E> {
+            let (len, bits) = v
+                .split_first_chunk::<4>()
+                .ok_or_else(|| E::invalid_length(v.len(), &self))?;
+            let len = u32::from_le_bytes(*len) as usize;
+            if bits.len() != len.div_ceil(8) {
+                return Err(E::invalid_length(v.len(), &self));
+            }
+            Ok((0..len)
+                .map(|i| bits[i / 8] & (1 << (i % 8)) != 0)
+                .collect())
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<bool>, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            self.visit_bytes(&bytes)
+        }
+    }
+}
+
+/// Stores a `Vec<i32>` as a [`Tag::IntArray`](crate::Tag::IntArray) without wrapping it in
+/// [`IntArray`](crate::IntArray). The elements are written back to back as little-endian words,
+/// and an empty vector becomes an array of length zero.
+pub mod int_array {
+    use serde::{Deserializer, Serializer};
+
+    use crate::array::{self, INT_ARRAY};
+
+    pub fn serialize<S: Serializer>(value: &[i32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(INT_ARRAY, value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<i32>, D::Error> {
+        array::deserialize_array(deserializer, INT_ARRAY)
+    }
+}
+
+/// Stores a `Vec<i64>` as a [`Tag::LongArray`](crate::Tag::LongArray), like [`int_array`].
+pub mod long_array {
+    use serde::{Deserializer, Serializer};
+
+    use crate::array::{self, LONG_ARRAY};
+
+    pub fn serialize<S: Serializer>(value: &[i64], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(LONG_ARRAY, value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<i64>, D::Error> {
+        array::deserialize_array(deserializer, LONG_ARRAY)
+    }
+}
+
+/// Stores a `[T; N]` of any length as a tuple, like serde does for arrays of up to 32 elements,
+/// so it is laid out under the configured [`TupleRepr`](crate::TupleRepr): a list by default, or
+/// a compound keyed by index with [`TupleRepr::Compound`](crate::TupleRepr::Compound). Decoding
+/// fails unless there are exactly `N` elements.
+pub mod array {
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::ser::SerializeTuple;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S, T, const N: usize>(value: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        let mut tuple = serializer.serialize_tuple(N)?;
+        for element in value {
+            tuple.serialize_element(element)?;
+        }
+        tuple.end()
+    }
+
+    pub fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        deserializer.deserialize_tuple(N, ArrayVisitor(PhantomData))
+    }
+
+    struct ArrayVisitor<T, const N: usize>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>, const N: usize> Visitor<'de> for ArrayVisitor<T, N> {
+        type Value = [T; N];
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "an array of {N} elements")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<[T; N], A::Error> {
+            let mut elements = Vec::with_capacity(N);
+            while let Some(element) = seq.next_element()? {
+                if elements.len() == N {
+                    return Err(de::Error::invalid_length(N + 1, &self));
+                }
+                elements.push(element);
+            }
+            let len = elements.len();
+            elements
+                .try_into()
+                .map_err(|_| de::Error::invalid_length(len, &self))
+        }
+    }
+}
+
+/// Stores a `[u8; N]` as a [`Tag::ByteArray`](crate::Tag::ByteArray) rather than a list of
+/// bytes. Decoding fails unless the byte array holds exactly `N` bytes.
+pub mod byte_array {
+    use std::fmt;
+
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        value: &[u8; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[u8; N], D::Error> {
+        deserializer.deserialize_bytes(ByteArrayVisitor)
+    }
+
+    struct ByteArrayVisitor<const N: usize>;
+
+    impl<'de, const N: usize> Visitor<'de> for ByteArrayVisitor<N> {
+        type Value = [u8; N];
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a byte array of length {N}")
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<[u8; N], E> {
+            v.try_into().map_err(|_| E::invalid_length(v.len(), &self))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<[u8; N], A::Error> {
+            let mut bytes = Vec::with_capacity(N);
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            self.visit_bytes(&bytes)
+        }
+    }
+}
+
+/// Stores a `String` as a [`Tag::ByteArray`](crate::Tag::ByteArray) of UTF-16LE code units, as
+/// used by some legacy formats, instead of a MUTF-8 string. Characters outside the BMP take a
+/// surrogate pair. Decoding fails on an odd number of bytes or an unpaired surrogate.
+pub mod utf16 {
+    use std::fmt;
+
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &str, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes: Vec<u8> = value.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        serializer.serialize_bytes(&bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+        deserializer.deserialize_byte_buf(Utf16Visitor)
+    }
+
+    struct Utf16Visitor;
+
+    impl<'de> Visitor<'de> for Utf16Visitor {
+        type Value = String;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a byte array of UTF-16LE code units")
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<String, E> {
+            let units = v.chunks_exact(2);
+            if !units.remainder().is_empty() {
+                return Err(E::invalid_length(v.len(), &self));
+            }
+            let units = units.map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+            char::decode_utf16(units)
+                .collect::<Result<String, _>>()
+                .map_err(|e| {
+                    E::invalid_value(
+                        de::Unexpected::Unsigned(e.unpaired_surrogate().into()),
+                        &self,
+                    )
+                })
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<String, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            self.visit_bytes(&bytes)
+        }
+    }
+}
+
+/// Compact encodings for `std::net` addresses, which serde otherwise writes as enums and tuples.
+///
+/// An IPv4 address is stored as a [`Tag::Int`](crate::Tag::Int) holding its bits, and an IPv6
+/// address as a 16 byte [`Tag::ByteArray`](crate::Tag::ByteArray). An IPv4-mapped IPv6 address
+/// such as `::ffff:192.0.2.1` is kept as IPv6 and decodes back to the same variant.
+pub mod net {
+    use std::fmt;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    use serde::de::{self, MapAccess, SeqAccess, Visitor};
+    use serde::ser::SerializeStruct;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Stores an `Ipv4Addr` as a `u32`.
+    pub mod ipv4 {
+        use std::net::Ipv4Addr;
+
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(addr: &Ipv4Addr, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u32(addr.to_bits())
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Ipv4Addr, D::Error> {
+            u32::deserialize(deserializer).map(Ipv4Addr::from_bits)
+        }
+    }
+
+    /// Stores an `Ipv6Addr` as 16 bytes in network order.
+    pub mod ipv6 {
+        use std::net::{IpAddr, Ipv6Addr};
+
+        use serde::de::Error;
+        use serde::{Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(addr: &Ipv6Addr, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(&addr.octets())
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Ipv6Addr, D::Error> {
+            deserializer
+                .deserialize_any(super::IpVisitor)
+                .and_then(|addr| match addr {
+                    IpAddr::V6(addr) => Ok(addr),
+                    IpAddr::V4(_) => Err(D::Error::custom("expected an IPv6 address")),
+                })
+        }
+    }
+
+    /// Stores an `IpAddr` with the tag of its version, so no enum variant is written.
+    pub mod ip {
+        use std::net::IpAddr;
+
+        use serde::{Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(addr: &IpAddr, serializer: S) -> Result<S::Ok, S::Error> {
+            match addr {
+                IpAddr::V4(addr) => super::ipv4::serialize(addr, serializer),
+                IpAddr::V6(addr) => super::ipv6::serialize(addr, serializer),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<IpAddr, D::Error> {
+            deserializer.deserialize_any(super::IpVisitor)
+        }
+    }
+
+    /// Stores a `SocketAddr` as a compound `{ip, port}`, with `ip` encoded as in [`ip`].
+    ///
+    /// The flow info and scope id of an IPv6 socket address are not stored.
+    pub mod socket_addr {
+        use std::net::SocketAddr;
+
+        use serde::{Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            addr: &SocketAddr,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            super::serialize_socket_addr(addr, serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<SocketAddr, D::Error> {
+            deserializer.deserialize_struct("SocketAddr", &["ip", "port"], super::SocketAddrVisitor)
+        }
+    }
+
+    struct Ip<'a>(&'a IpAddr);
+
+    impl Serialize for Ip<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            ip::serialize(self.0, serializer)
+        }
+    }
+
+    struct IpField(IpAddr);
+
+    impl<'de> Deserialize<'de> for IpField {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            ip::deserialize(deserializer).map(IpField)
+        }
+    }
+
+    fn serialize_socket_addr<S: Serializer>(
+        addr: &SocketAddr,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut compound = serializer.serialize_struct("SocketAddr", 2)?;
+        compound.serialize_field("ip", &Ip(&addr.ip()))?;
+        compound.serialize_field("port", &addr.port())?;
+        compound.end()
+    }
+
+    /// Decodes an IPv4 address from an integer and an IPv6 address from 16 bytes.
+    struct IpVisitor;
+
+    impl<'de> Visitor<'de> for IpVisitor {
+        type Value = IpAddr;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an IPv4 address as an integer or an IPv6 address as 16 bytes")
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<IpAddr, E> {
+            // An Int is signed, so the high bit of the address comes back as the sign.
+            let bits = i32::try_from(v)
+                .map(|v| v as u32)
+                .map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))?;
+            Ok(Ipv4Addr::from_bits(bits).into())
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<IpAddr, E> {
+            let bits = u32::try_from(v)
+                .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))?;
+            Ok(Ipv4Addr::from_bits(bits).into())
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<IpAddr, E> {
+            let octets: [u8; 16] = v
+                .try_into()
+                .map_err(|_| E::invalid_length(v.len(), &self))?;
+            Ok(Ipv6Addr::from(octets).into())
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<IpAddr, A::Error> {
+            let mut octets = [0; 16];
+            for (i, octet) in octets.iter_mut().enumerate() {
+                *octet = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+            }
+            if seq.next_element::<de::IgnoredAny>()?.is_some() {
+                Err(de::Error::invalid_length(17, &self))?
+            }
+            Ok(Ipv6Addr::from(octets).into())
+        }
+    }
+
+    struct SocketAddrVisitor;
+
+    impl<'de> Visitor<'de> for SocketAddrVisitor {
+        type Value = SocketAddr;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a socket address")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<SocketAddr, A::Error> {
+            let mut ip = None;
+            let mut port = None;
+            while let Some(key) = map.next_key::<std::borrow::Cow<str>>()? {
+                match &*key {
+                    "ip" => ip = Some(map.next_value::<IpField>()?.0),
+                    "port" => port = Some(map.next_value::<u16>()?),
+                    _ => {
+                        map.next_value::<de::IgnoredAny>()?;
+                    }
+                }
+            }
+            let ip = ip.ok_or_else(|| de::Error::missing_field("ip"))?;
+            let port = port.ok_or_else(|| de::Error::missing_field("port"))?;
+            Ok(SocketAddr::new(ip, port))
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Hand-checked reference encodings. Each body below is a complete document once the header is
+//! prepended and the document terminator appended.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{from_slice, to_vec, IntArray, LongArray, Value};
+
+const HEADER: [u8; 7] = [0xad, 0x4e, 0x42, 0x54, 0x00, 0x05, 0x00];
+
+fn document(body: &[u8]) -> Vec<u8> {
+    let mut doc = HEADER.to_vec();
+    doc.extend_from_slice(body);
+    doc.push(0x00);
+    doc
+}
+
+fn compound<const N: usize>(fields: [(&str, Value); N]) -> Value {
+    Value::Compound(fields.map(|(k, v)| (k.to_owned(), v)).into())
+}
+
+#[rustfmt::skip]
+fn vectors() -> Vec<(Value, Vec<u8>)> {
+    vec![
+        (Value::Byte(-1), vec![0x01, 0x00, 0x00, 0xff]),
+        (Value::Short(0x1234), vec![0x02, 0x00, 0x00, 0x34, 0x12]),
+        (Value::Int(-2), vec![0x03, 0x00, 0x00, 0xfe, 0xff, 0xff, 0xff]),
+        (Value::Long(1), vec![0x04, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+        (Value::Float(1.0), vec![0x05, 0x00, 0x00, 0x00, 0x00, 0x80, 0x3f]),
+        (Value::Double(-2.0), vec![0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0]),
+        (Value::ByteArray(vec![1, 2]), vec![0x07, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x02]),
+        (Value::ByteArray(vec![]), vec![0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+        (Value::String("".into()), vec![0x08, 0x00, 0x00, 0x00, 0x00]),
+        (Value::String("hi".into()), vec![0x08, 0x00, 0x00, 0x02, 0x00, b'h', b'i']),
+        // Two byte sequence.
+        (Value::String("\u{e9}".into()), vec![0x08, 0x00, 0x00, 0x02, 0x00, 0xc3, 0xa9]),
+        // MUTF-8 writes nulls as an overlong two byte sequence.
+        (Value::String("\0".into()), vec![0x08, 0x00, 0x00, 0x02, 0x00, 0xc0, 0x80]),
+        // Characters outside the BMP become a surrogate pair of three byte sequences.
+        (
+            Value::String("\u{1f30d}".into()),
+            vec![0x08, 0x00, 0x00, 0x06, 0x00, 0xed, 0xa0, 0xbc, 0xed, 0xbc, 0x8d],
+        ),
+        // An empty list has the element tag End.
+        (Value::List(vec![]), vec![0x09, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+        (
+            Value::List(vec![Value::Short(1), Value::Short(2)]),
+            vec![0x09, 0x00, 0x00, 0x02, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x02, 0x00],
+        ),
+        (
+            Value::List(vec![Value::List(vec![Value::Byte(1)]), Value::List(vec![])]),
+            vec![
+                0x09, 0x00, 0x00, 0x09, 0x02, 0x00, 0x00, 0x00,
+                0x01, 0x01, 0x00, 0x00, 0x00, 0x01,
+                0x00, 0x00, 0x00, 0x00, 0x00,
+            ],
+        ),
+        (compound([]), vec![0x0a, 0x00, 0x00, 0x00]),
+        (
+            compound([
+                ("a", Value::Byte(1)),
+                ("n", compound([("s", Value::String("x".into()))])),
+            ]),
+            vec![
+                0x0a, 0x00, 0x00,
+                0x01, 0x01, 0x00, b'a', 0x01,
+                0x0a, 0x01, 0x00, b'n',
+                0x08, 0x01, 0x00, b's', 0x01, 0x00, b'x',
+                0x00,
+                0x00,
+            ],
+        ),
+        (
+            compound([(
+                "l",
+                Value::List(vec![compound([("b", Value::Byte(2))]), compound([])]),
+            )]),
+            vec![
+                0x0a, 0x00, 0x00,
+                0x09, 0x01, 0x00, b'l', 0x0a, 0x02, 0x00, 0x00, 0x00,
+                0x01, 0x01, 0x00, b'b', 0x02, 0x00,
+                0x00,
+                0x00,
+            ],
+        ),
+    ]
+}
+
+#[test]
+fn header() {
+    assert_eq!(
+        to_vec(&compound([])).unwrap(),
+        [0xad, 0x4e, 0x42, 0x54, 0x00, 0x05, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x00]
+    );
+}
+
+#[test]
+fn values() {
+    for (value, body) in vectors() {
+        let doc = document(&body);
+        assert_eq!(to_vec(&value).unwrap(), doc, "encoding {value}");
+        assert_eq!(
+            from_slice::<Value>(&doc).unwrap(),
+            value,
+            "decoding {value}"
+        );
+    }
+}
+
+#[rustfmt::skip]
+#[test]
+fn typed() {
+    fn check<T: Serialize>(value: T, body: &[u8]) {
+        assert_eq!(to_vec(&value).unwrap(), document(body));
+    }
+
+    check(true, &[0x01, 0x00, 0x00, 0x01]);
+    check(u16::MAX, &[0x02, 0x00, 0x00, 0xff, 0xff]);
+    check('x', &[0x08, 0x00, 0x00, 0x01, 0x00, b'x']);
+    check(
+        IntArray(vec![1, -1]),
+        &[0x0b, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff],
+    );
+    check(IntArray(vec![]), &[0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    check(
+        LongArray(vec![2]),
+        &[0x0c, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    );
+    check(
+        BTreeMap::from([("k", "v")]),
+        &[0x0a, 0x00, 0x00, 0x08, 0x01, 0x00, b'k', 0x01, 0x00, b'v', 0x00],
+    );
+}
+
+#[test]
+fn big_endian() {
+    let mut doc = document(&[0x03, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04]);
+    doc[6] = 0x80;
+    assert_eq!(from_slice::<Value>(&doc).unwrap(), Value::Int(0x0102_0304));
+}
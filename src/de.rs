@@ -1,102 +1,1526 @@
-use std::io::Read;
+use std::borrow::Cow;
+use std::io;
+use std::marker::PhantomData;
+use std::ops::Range;
 
-use serde::de::{self, DeserializeOwned};
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{self, Deserialize, DeserializeOwned, IntoDeserializer};
 
+use crate::array;
 use crate::error::{Error, Result};
+use crate::read::{IoRead, Read, SliceRead};
+use crate::{EnumRepr, OptionRepr, Tag, TupleRepr, Value};
 
-pub fn from_slice<T: DeserializeOwned>(input: &[u8]) -> Result<T> {
-    from_reader(input)
+pub fn from_slice<'de, T: Deserialize<'de>>(input: &'de [u8]) -> Result<T> {
+    let mut deserializer = Deserializer::from_slice(input)?;
+    T::deserialize(&mut deserializer)
 }
 
-pub fn from_reader<R: Read, T: DeserializeOwned>(input: R) -> Result<T> {
-    let mut deserializer = Deserializer::new(input)?;
+pub fn from_reader<R: io::Read, T: DeserializeOwned>(input: R) -> Result<T> {
+    let mut deserializer = Deserializer::from_reader(input)?;
     T::deserialize(&mut deserializer)
 }
 
-pub struct Deserializer<R: Read> {
+/// Decodes one document from `input` like [`from_reader`], also consuming the terminator after
+/// the root and returning the total number of bytes read, header included.
+///
+/// Nothing past the document is read, so `input` is left at the start of whatever follows it,
+/// such as the next document in a stream.
+pub fn from_reader_counted<R: io::Read, T: DeserializeOwned>(input: R) -> Result<(T, usize)> {
+    let mut deserializer = Deserializer::from_reader(input)?;
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer.end_root()?;
+    Ok((value, deserializer.input.consumed()))
+}
+
+/// Maps the file at `path` into memory and decodes it with the borrowing slice deserializer.
+///
+/// # Safety
+///
+/// The file must not be modified or truncated, by this process or any other, until the call
+/// returns. A file that shrinks while it is mapped makes reading the mapping undefined
+/// behavior, which typically shows up as a `SIGBUS`.
+#[cfg(feature = "mmap")]
+pub unsafe fn from_mmap<P: AsRef<std::path::Path>, T: DeserializeOwned>(path: P) -> Result<T> {
+    let file = std::fs::File::open(path)?;
+    // SAFETY: the map is only read for the duration of this call, and the caller guarantees the
+    // file isn't changed underneath it.
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    from_slice(&map)
+}
+
+/// Checks that `input` holds exactly one well-formed document, walking every tag, name, length
+/// and terminator without decoding any values. See [`Deserializer::validate`].
+pub fn validate(input: &[u8]) -> Result<()> {
+    Deserializer::from_slice(input)?.validate()
+}
+
+/// Lists the names of the root compound's fields, skipping over their values without decoding
+/// them.
+pub fn root_keys<R: io::Read>(input: R) -> Result<Vec<String>> {
+    let mut deserializer = Deserializer::from_reader(input)?;
+    deserializer.expect_tag(Tag::Compound)?;
+    let mut keys = Vec::new();
+    loop {
+        let tag = deserializer.read_tag()?;
+        if tag == Tag::End {
+            break Ok(keys);
+        }
+        keys.push(deserializer.read_name()?.into_owned());
+        deserializer.skip_value(tag)?;
+    }
+}
+
+/// One step of a path passed to [`get_path`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathSeg<'a> {
+    /// A field of a compound.
+    Key(&'a str),
+    /// An element of a list or of a byte, int or long array.
+    Index(usize),
+}
+
+/// Decodes only the value at `path`, skipping over everything in front of it.
+///
+/// An empty path decodes the root. Returns `Ok(None)` if the path doesn't lead to a value: a key
+/// is missing, an index is out of range, or a step finds a value of the wrong kind, such as a
+/// key applied to a list. Errors are reserved for input that is malformed or that doesn't
+/// decode as `T`.
+pub fn get_path<R: io::Read, T: DeserializeOwned>(input: R, path: &[PathSeg]) -> Result<Option<T>> {
+    let mut deserializer = Deserializer::from_reader(input)?;
+    let mut tag = deserializer.next_tag()?;
+    for seg in path {
+        tag = match (*seg, tag) {
+            (PathSeg::Key(key), Tag::Compound) => loop {
+                let field = deserializer.read_tag()?;
+                if field == Tag::End {
+                    return Ok(None);
+                }
+                if deserializer.read_name()? == key {
+                    break field;
+                }
+                deserializer.skip_value(field)?;
+            },
+            (PathSeg::Index(index), Tag::List) => {
+                let element = deserializer.read_tag()?;
+                if index >= deserializer.read_list_len()? {
+                    return Ok(None);
+                }
+                for _ in 0..index {
+                    deserializer.skip_value(element)?;
+                }
+                element
+            }
+            (PathSeg::Index(index), Tag::ByteArray | Tag::IntArray | Tag::LongArray) => {
+                let (element, width) = match tag {
+                    Tag::ByteArray => (Tag::Byte, 1),
+                    Tag::IntArray => (Tag::Int, 4),
+                    _ => (Tag::Long, 8),
+                };
+                if index >= deserializer.read_len()? {
+                    return Ok(None);
+                }
+                deserializer.skip_bytes(index * width)?;
+                element
+            }
+            _ => return Ok(None),
+        };
+    }
+    deserializer.tag = Some(tag);
+    T::deserialize(&mut deserializer).map(Some)
+}
+
+/// Decodes a ShadeNBT document from a [`Read`] source.
+///
+/// Sets such as `HashSet` and `BTreeSet` are stored as lists. Nothing checks that a decoded
+/// list is free of duplicates; repeated elements are merged by the set as it is built.
+///
+/// Every value is self-describing, so `#[serde(flatten)]` works: fields that no named field
+/// claims are collected into a flattened map such as `HashMap<String, Value>`, where a key that
+/// appears twice keeps its last value.
+pub struct Deserializer<R> {
     input: R,
-    endianness: bool,
+    header: HeaderInfo,
+    /// Cleared by the `0x80` flag in the header, which marks big-endian numbers.
+    little_endian: bool,
+    /// The names defined so far, indexed by their references, if the document interns them.
+    names: Option<Vec<String>>,
+    tag: Option<Tag>,
+    /// The root's tag, once it has been read.
+    root: Option<Tag>,
+    /// Set while reading the payload of an internally tagged newtype variant, whose compound has
+    /// already been opened by the tag.
+    inline: Option<String>,
+    max_string_len: usize,
+    max_fields: usize,
+    /// The number of lists and compounds currently being read, checked against `max_depth`.
+    depth: usize,
+    max_depth: usize,
+    lenient_mutf8: bool,
+    coerce_integers: bool,
+    coerce_floats: bool,
+    strict_bools: bool,
+    enum_repr: EnumRepr,
+    tuple_repr: TupleRepr,
+    option_repr: OptionRepr,
+    human_readable: bool,
+    /// Handlers for tags beyond the standard ones, added by [`Deserializer::register_tag`].
+    custom_tags: Vec<(u8, TagHandler)>,
+    /// A field with a custom tag whose name has been read but whose payload hasn't.
+    custom: Option<(u8, TagHandler)>,
+    /// Set by [`Deserializer::new_bare`], whose documents have no terminator after the root.
+    bare: bool,
+}
+
+/// Reads the payload of a custom tag registered with [`Deserializer::register_tag`].
+///
+/// The handler is given the input positioned just after the field's name and must read exactly
+/// the payload with [`read_exact`](io::Read::read_exact), no more and no less. The deserializer
+/// can't tell where the payload really ends, so a handler that misreads it leaves the rest of
+/// the document misaligned, which typically surfaces as a confusing error further on.
+pub type TagHandler = fn(&mut dyn io::Read) -> Result<Value>;
+
+/// Lends the deserializer's input to a [`TagHandler`].
+struct HandlerInput<'a, R>(&'a mut R);
+
+impl<'de, R: Read<'de>> io::Read for HandlerInput<'_, R> {
+    /// Fills all of `buf` or fails, since [`Read`] has no way to report a short read.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read_exact(buf).map_err(|e| match e {
+            Error::Eof => io::ErrorKind::UnexpectedEof.into(),
+            Error::Io(e) => e,
+            e => io::Error::other(e),
+        })?;
+        Ok(buf.len())
+    }
+}
+
+impl<R: io::Read> Deserializer<IoRead<R>> {
+    pub fn from_reader(reader: R) -> Result<Self> {
+        Deserializer::new(IoRead::new(reader))
+    }
+
+    /// Consumes the terminator after a decoded root and returns the reader, positioned at the
+    /// first byte past the document so the caller can go on parsing whatever follows it.
+    ///
+    /// Nothing is ever read ahead, so no bytes past the document are lost. If the root hasn't
+    /// been decoded yet, the reader is returned wherever it stopped.
+    pub fn trailing(mut self) -> Result<R> {
+        self.end_root()?;
+        Ok(self.input.into_inner())
+    }
+}
+
+impl<'a> Deserializer<SliceRead<'a>> {
+    pub fn from_slice(slice: &'a [u8]) -> Result<Self> {
+        Deserializer::new(SliceRead::new(slice))
+    }
+
+    /// Consumes the terminator after a decoded root and returns the bytes that follow the
+    /// document, like [`Deserializer::trailing`] does for a reader.
+    pub fn trailing(mut self) -> Result<&'a [u8]> {
+        self.end_root()?;
+        Ok(self.input.into_rest())
+    }
+
+    /// Measures the document at the start of `slice`, header and terminator included, by
+    /// walking it without decoding anything. Fails with [`Error::Eof`] or
+    /// [`Error::TruncatedHeader`] if `slice` ends before the document does.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn document_len(slice: &'a [u8]) -> Result<usize> {
+        let mut de = Deserializer::from_slice(slice)?;
+        let tag = de.next_tag()?;
+        de.skip_value(tag)?;
+        de.end_root()?;
+        Ok(slice.len() - de.input.remaining())
+    }
+
+    /// Walks `path` from the root of the document in `slice`, naming compound fields by key and
+    /// list elements by index, without decoding anything along the way.
+    pub(crate) fn locate(slice: &'a [u8], path: &[&str]) -> Result<Location> {
+        let mut de = Deserializer::from_slice(slice)?;
+        let offset = |de: &Self| slice.len() - de.input.remaining();
+        let mut tag_offset = Some(offset(&de));
+        let mut tag = de.read_tag()?;
+        de.read_string()?;
+        for (depth, key) in path.iter().enumerate() {
+            let not_found = || Error::FieldNotFound(path[..=depth].join("/"));
+            match tag {
+                Tag::Compound => loop {
+                    let field_offset = offset(&de);
+                    let field = de.read_tag()?;
+                    if field == Tag::End {
+                        Err(not_found())?
+                    }
+                    if de.read_string()? == *key {
+                        tag_offset = Some(field_offset);
+                        tag = field;
+                        break;
+                    }
+                    de.skip_value(field)?;
+                },
+                Tag::List => {
+                    let element = de.read_tag()?;
+                    let len = de.read_list_len()?;
+                    let index = key
+                        .parse()
+                        .ok()
+                        .filter(|&i| i < len)
+                        .ok_or_else(not_found)?;
+                    for _ in 0..index {
+                        de.skip_value(element)?;
+                    }
+                    tag_offset = None;
+                    tag = element;
+                }
+                _ => Err(not_found())?,
+            }
+        }
+        let start = offset(&de);
+        de.skip_value(tag)?;
+        Ok(Location {
+            tag_offset,
+            tag,
+            payload: start..offset(&de),
+            little_endian: de.little_endian,
+        })
+    }
+
+    /// Splits the compound whose payload starts at offset `start` of the document in `slice`
+    /// into its fields, without decoding any of them.
+    pub(crate) fn fields(slice: &'a [u8], start: usize) -> Result<Vec<Field>> {
+        let mut de = Deserializer::from_slice(slice)?;
+        let offset = |de: &Self| slice.len() - de.input.remaining();
+        de.skip_bytes(start - offset(&de))?;
+        let mut fields = Vec::new();
+        loop {
+            let header = offset(&de);
+            let tag = de.read_tag()?;
+            if tag == Tag::End {
+                break Ok(fields);
+            }
+            let name = de.read_name()?.into_owned();
+            let payload = offset(&de);
+            de.skip_value(tag)?;
+            fields.push(Field {
+                tag,
+                name,
+                header: header..payload,
+                payload: payload..offset(&de),
+            });
+        }
+    }
+}
+
+/// A field of an encoded compound, as split out by [`Deserializer::fields`].
+pub(crate) struct Field {
+    pub tag: Tag,
+    pub name: String,
+    /// The field's tag and name.
+    pub header: Range<usize>,
+    pub payload: Range<usize>,
+}
+
+/// Where a value sits in an encoded document.
+pub(crate) struct Location {
+    /// The offset of the value's tag, or `None` for a list element, whose tag is shared.
+    pub tag_offset: Option<usize>,
+    pub tag: Tag,
+    pub payload: Range<usize>,
+    pub little_endian: bool,
+}
+
+/// The flags from a document header, as returned by [`read_header`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HeaderInfo {
+    /// The format version from the two bytes after the magic, read big-endian. Version 5 is
+    /// plain ShadeNBT, version 6 the same with interned names, as written by
+    /// [`Serializer::new_interned`](crate::Serializer::new_interned), and version 7 the same
+    /// with compact list lengths, as written by
+    /// [`Serializer::new_compact_lists`](crate::Serializer::new_compact_lists). No other
+    /// version is accepted.
+    pub version: u16,
+    pub flags: u8,
 }
 
-impl<R: Read> Deserializer<R> {
-    fn new(mut input: R) -> Result<Self> {
+/// How deeply lists and compounds may be nested by default, as set by
+/// [`Deserializer::set_max_depth`].
+pub(crate) const DEFAULT_MAX_DEPTH: usize = 512;
+
+/// The header version of documents with interned names.
+pub(crate) const INTERNED_VERSION: u8 = 6;
+
+/// The header version of documents whose list lengths are variable-length integers.
+pub(crate) const COMPACT_LISTS_VERSION: u8 = 7;
+
+/// Set on an interned name reference, whose low 15 bits are then the name's index. Otherwise
+/// the `u16` is the length of a new name that follows.
+pub(crate) const NAME_REF: u16 = 0x8000;
+
+impl HeaderInfo {
+    /// Reads the header a byte at a time, so that input ending partway through it can be told
+    /// apart from input that isn't ShadeNBT at all.
+    fn read<'de, R: Read<'de>>(input: &mut R) -> Result<Self> {
+        const MAGIC: [u8; 5] = [0xAD, 0x4E, 0x42, 0x54, 0x00];
         let mut buf = [0; 7];
-        input.read_exact(&mut buf)?;
-        if buf[0..6] != [0xAD, 0x4E, 0x42, 0x54, 0x00, 0x04] {
-            Err(Error::InvalidHeader)?
+        for len in 0..buf.len() {
+            match input.read_exact(&mut buf[len..=len]) {
+                Ok(()) => {}
+                Err(Error::Eof) if len == 0 => Err(Error::Eof)?,
+                Err(Error::Eof) => Err(Error::TruncatedHeader(len))?,
+                Err(e) => Err(e)?,
+            }
+            let valid = match len {
+                0..5 => buf[len] == MAGIC[len],
+                5 => matches!(buf[len], 5 | INTERNED_VERSION | COMPACT_LISTS_VERSION),
+                _ => true,
+            };
+            if !valid {
+                Err(Error::InvalidHeader)?
+            }
         }
         Ok(Self {
+            version: u16::from_be_bytes([buf[4], buf[5]]),
+            flags: buf[6],
+        })
+    }
+
+    /// Whether the document stores its numbers big-endian.
+    pub fn is_big_endian(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+
+    /// Whether field names after their first use are written as references to it.
+    pub fn has_interned_names(&self) -> bool {
+        self.version == u16::from(INTERNED_VERSION)
+    }
+
+    /// Whether list lengths are stored in LEB128 rather than as an `i32`.
+    pub fn has_compact_lists(&self) -> bool {
+        self.version == u16::from(COMPACT_LISTS_VERSION)
+    }
+}
+
+/// Reads and checks the header at the start of `input`, leaving it positioned at the root tag
+/// to be decoded by [`Deserializer::headless`].
+///
+/// `input` must be at the very start of the header. One positioned partway through it fails
+/// with [`Error::InvalidHeader`], just like input that isn't ShadeNBT. Empty input fails with
+/// [`Error::Eof`], and input that ends within an otherwise valid header with
+/// [`Error::TruncatedHeader`].
+pub fn read_header<R: io::Read>(input: R) -> Result<HeaderInfo> {
+    HeaderInfo::read(&mut IoRead::new(input))
+}
+
+impl<'de, R: Read<'de>> Deserializer<R> {
+    /// Reads the header from `input`, failing as described for [`read_header`].
+    pub fn new(mut input: R) -> Result<Self> {
+        let header = HeaderInfo::read(&mut input)?;
+        Ok(Self::headless(input, header))
+    }
+
+    /// Creates a deserializer for a bare tag stream as written by
+    /// [`Serializer::new_bare`](crate::Serializer::new_bare), which starts at the root tag and
+    /// has no terminator after the root. Numbers are read little-endian.
+    ///
+    /// [`from_slice`] and the other constructors always expect a header; this is the only way
+    /// to read a bare stream. Since bare documents have no header to look for,
+    /// [`into_iter`](Self::into_iter) can't find where one ends and the next begins.
+    pub fn new_bare(input: R) -> Self {
+        let mut de = Self::headless(
             input,
-            endianness: buf[6] & 0x80 != 0,
+            HeaderInfo {
+                version: 5,
+                flags: 0,
+            },
+        );
+        de.bare = true;
+        de
+    }
+
+    /// Creates a deserializer for a document whose header was already consumed by
+    /// [`read_header`], so `input` starts at the root tag.
+    pub fn headless(input: R, header: HeaderInfo) -> Self {
+        Self {
+            input,
+            header,
+            little_endian: !header.is_big_endian(),
+            names: header.has_interned_names().then(Vec::new),
+            tag: None,
+            root: None,
+            inline: None,
+            max_string_len: usize::MAX,
+            max_fields: usize::MAX,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            lenient_mutf8: false,
+            coerce_integers: false,
+            coerce_floats: false,
+            strict_bools: false,
+            enum_repr: EnumRepr::default(),
+            tuple_repr: TupleRepr::default(),
+            option_repr: OptionRepr::default(),
+            human_readable: false,
+            custom_tags: Vec::new(),
+            custom: None,
+            bare: false,
+        }
+    }
+
+    /// The header the document was read with, or the one given to [`Self::headless`].
+    pub fn header(&self) -> HeaderInfo {
+        self.header
+    }
+
+    /// Sets the maximum length, in encoded bytes, accepted for a string or byte array.
+    ///
+    /// Longer values are rejected with [`Error::LimitExceeded`] before anything is allocated.
+    pub fn set_max_string_len(&mut self, len: usize) {
+        self.max_string_len = len;
+    }
+
+    /// Sets the maximum number of fields read from any one compound into a map or struct.
+    ///
+    /// The field past the limit is rejected with [`Error::LimitExceeded`] before its name is
+    /// read. Each compound is counted on its own, so the total size of a document made of many
+    /// small compounds is not bounded by this.
+    pub fn set_max_fields(&mut self, fields: usize) {
+        self.max_fields = fields;
+    }
+
+    /// Sets how deeply lists and compounds may be nested inside each other, which bounds the
+    /// recursion used to read them. Deeper input fails with [`Error::TooDeep`].
+    ///
+    /// The default of 512 matches the limit applied by Minecraft.
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    /// Walks the rest of the document without decoding it, checking every tag, name and length
+    /// along with the string, field and depth limits, and that nothing follows the document's
+    /// terminator.
+    ///
+    /// Nothing is allocated except the table of an interned document's names.
+    pub fn validate(&mut self) -> Result<()> {
+        let tag = self.next_tag()?;
+        self.skip_value(tag)?;
+        self.end_root()?;
+        match self.input.read_exact(&mut [0]) {
+            Ok(()) => Err(Error::TrailingBytes),
+            Err(Error::Eof) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Accepts the otherwise unknown `tag` as the tag of compound fields, reading their payloads
+    /// with `handler`. Registering a tag again replaces its handler. The standard tags can't be
+    /// overridden, and registering one has no effect.
+    ///
+    /// A field with a custom tag decodes as the [`Value`] the handler returns when it is read
+    /// into a `Value`, or into anything else that accepts any type of value, and is skipped like
+    /// any other field when ignored. Read into a specific type such as an integer, it fails with
+    /// [`Error::UnknownTag`]. Custom tags are only recognized on compound fields; at the root or
+    /// as the element tag of a list they fail the same way unregistered tags do.
+    pub fn register_tag(&mut self, tag: u8, handler: TagHandler) {
+        self.custom_tags.retain(|&(t, _)| t != tag);
+        self.custom_tags.push((tag, handler));
+    }
+
+    /// Chooses how strings that are not valid MUTF-8 are handled.
+    ///
+    /// By default they are rejected with [`Error::Mutf8`], including overlong encodings and lone
+    /// surrogates. When lenient, invalid sequences are replaced with U+FFFD instead, so decoding
+    /// a string never fails on its contents.
+    pub fn set_lenient_mutf8(&mut self, lenient: bool) {
+        self.lenient_mutf8 = lenient;
+    }
+
+    /// Lets integers stored with any integer tag be read into an integer of a different width.
+    ///
+    /// The stored value is read with the signedness of the target type and must fit into it, so
+    /// a `Short` can be widened into a `u32` field but an `Int` of 70000 can't be narrowed into a
+    /// `u16`. By default the tag must match the width of the target exactly.
+    pub fn set_coerce_integers(&mut self, coerce: bool) {
+        self.coerce_integers = coerce;
+    }
+
+    /// Lets a `Float` be read into an `f64` and a `Double` into an `f32`.
+    ///
+    /// Widening is exact. Narrowing rounds to the nearest `f32`, so precision is lost, and a
+    /// `Double` beyond the range of `f32` becomes an infinity of the same sign. By default the tag
+    /// must match the target exactly.
+    pub fn set_coerce_floats(&mut self, coerce: bool) {
+        self.coerce_floats = coerce;
+    }
+
+    /// Requires every `bool` to be stored as exactly `0` or `1`, failing with
+    /// [`Error::InvalidBool`] otherwise. By default any nonzero byte reads as `true`. Integer
+    /// fields are unaffected, so a byte of `2` still reads as a `u8`.
+    pub fn set_strict_bools(&mut self, strict: bool) {
+        self.strict_bools = strict;
+    }
+
+    pub fn set_enum_repr(&mut self, repr: EnumRepr) {
+        self.enum_repr = repr;
+    }
+
+    pub fn set_tuple_repr(&mut self, repr: TupleRepr) {
+        self.tuple_repr = repr;
+    }
+
+    pub fn set_option_repr(&mut self, repr: OptionRepr) {
+        self.option_repr = repr;
+    }
+
+    /// Sets what [`is_human_readable`](de::Deserializer::is_human_readable) reports, which must
+    /// match the mode the document was serialized with. See
+    /// [`Serializer::set_human_readable`](crate::Serializer::set_human_readable).
+    pub fn set_human_readable(&mut self, human_readable: bool) {
+        self.human_readable = human_readable;
+    }
+
+    /// Starts reading the next value, which must be a list or an int or long array, one element
+    /// at a time.
+    ///
+    /// Only the element being decoded is held in memory. Once the iterator has returned every
+    /// element, the input is positioned just after the list.
+    pub fn list_iter<T: Deserialize<'de>>(&mut self) -> Result<ListIter<'_, R, T>> {
+        let (tag, remaining) = match self.next_tag()? {
+            Tag::List => (self.read_tag()?, self.read_list_len()?),
+            Tag::IntArray => (Tag::Int, self.read_len()?),
+            Tag::LongArray => (Tag::Long, self.read_len()?),
+            found => Err(Error::TagMismatch(Tag::List, found))?,
+        };
+        Ok(ListIter {
+            de: self,
+            tag,
+            remaining,
+            element: PhantomData,
+        })
+    }
+
+    /// Turns the deserializer into an iterator decoding a stream of documents, each with its own
+    /// header, one after another. It ends cleanly when the input ends where the next header
+    /// would start.
+    ///
+    /// The first document is read with the header this deserializer was created with. Each later
+    /// one may use a different byte order or name interning, and starts with an empty name
+    /// table, while the limits and representations set on the deserializer apply to all of them.
+    /// The iterator stops after the first error, including input that ends within a document.
+    #[allow(clippy::should_implement_trait)]
+    pub fn into_iter<T: Deserialize<'de>>(self) -> DocumentIter<R, T> {
+        DocumentIter {
+            de: self,
+            started: false,
+            done: false,
+            document: PhantomData,
+        }
+    }
+
+    /// Reads the header of the next document in a stream, resetting everything that belonged to
+    /// the previous one. Returns `false` if the input has ended instead.
+    pub(crate) fn next_document(&mut self) -> Result<bool> {
+        let header = match HeaderInfo::read(&mut self.input) {
+            Ok(header) => header,
+            Err(Error::Eof) => return Ok(false),
+            Err(e) => Err(e)?,
+        };
+        self.header = header;
+        self.little_endian = !header.is_big_endian();
+        self.names = header.has_interned_names().then(Vec::new);
+        self.tag = None;
+        self.root = None;
+        self.inline = None;
+        self.custom = None;
+        self.depth = 0;
+        Ok(true)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut buf = [0; N];
+        self.input.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16> {
+        let buf = self.read_array()?;
+        Ok(if self.little_endian {
+            u16::from_le_bytes(buf)
+        } else {
+            u16::from_be_bytes(buf)
+        })
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let buf = self.read_array()?;
+        Ok(if self.little_endian {
+            u32::from_le_bytes(buf)
+        } else {
+            u32::from_be_bytes(buf)
         })
     }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        let buf = self.read_array()?;
+        Ok(if self.little_endian {
+            u64::from_le_bytes(buf)
+        } else {
+            u64::from_be_bytes(buf)
+        })
+    }
+
+    /// Reads a 128-bit integer, which is stored as a 16 byte array.
+    fn read_u128(&mut self) -> Result<u128> {
+        self.expect_tag(Tag::ByteArray)?;
+        let len = self.read_len()?;
+        if len != 16 {
+            Err(<Error as de::Error>::invalid_length(len, &"16 bytes"))?
+        }
+        // The value is the body of a byte array, which has no byte order to swap, so it stays
+        // little-endian even in a big-endian document.
+        Ok(u128::from_le_bytes(self.read_array()?))
+    }
+
+    /// Reads an integer stored with the width tag `expected`, or with any integer tag when
+    /// coercion is enabled, extending it according to `signed`.
+    fn read_integer(&mut self, expected: Tag, signed: bool) -> Result<i128> {
+        let found = self.next_tag()?;
+        let integer = matches!(found, Tag::Byte | Tag::Short | Tag::Int | Tag::Long);
+        if found != expected && !(self.coerce_integers && integer) {
+            Err(Error::TagMismatch(expected, found))?
+        }
+        Ok(match found {
+            Tag::Byte if signed => (self.read_u8()? as i8).into(),
+            Tag::Byte => self.read_u8()?.into(),
+            Tag::Short if signed => (self.read_u16()? as i16).into(),
+            Tag::Short => self.read_u16()?.into(),
+            Tag::Int if signed => (self.read_u32()? as i32).into(),
+            Tag::Int => self.read_u32()?.into(),
+            Tag::Long if signed => (self.read_u64()? as i64).into(),
+            Tag::Long => self.read_u64()?.into(),
+            _ => unreachable!("only integer tags are accepted"),
+        })
+    }
+
+    /// Reads the tag of a float stored as `expected`, or as either float tag when coercion is
+    /// enabled, returning the tag found.
+    fn read_float(&mut self, expected: Tag) -> Result<Tag> {
+        let found = self.next_tag()?;
+        if found != expected && !(self.coerce_floats && matches!(found, Tag::Float | Tag::Double)) {
+            Err(Error::TagMismatch(expected, found))?
+        }
+        Ok(found)
+    }
+
+    /// Reads an `i32` length prefix, rejecting negative lengths instead of letting them wrap to
+    /// huge `usize`s. No memory is reserved based on the length alone.
+    fn read_len(&mut self) -> Result<usize> {
+        let len = self.read_u32()? as i32;
+        usize::try_from(len).map_err(|_| Error::NegativeLen(len))
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Cow<'de, [u8]>> {
+        if len > self.max_string_len {
+            Err(Error::LimitExceeded(len, self.max_string_len))?
+        }
+        self.input.read_bytes(len)
+    }
+
+    /// Reads the element count of a list. Documents with compact list lengths store it in LEB128
+    /// of at most five bytes, which must not exceed `i32::MAX`; others as an `i32` like any other
+    /// length.
+    fn read_list_len(&mut self) -> Result<usize> {
+        if !self.header.has_compact_lists() {
+            return self.read_len();
+        }
+        let mut len = 0u64;
+        for i in 0..5 {
+            let byte = self.read_u8()?;
+            len |= u64::from(byte & 0x7f) << (7 * i);
+            if byte & 0x80 == 0 {
+                return match i32::try_from(len) {
+                    Ok(len) => Ok(len as usize),
+                    Err(_) => Err(Error::InvalidListLen),
+                };
+            }
+        }
+        Err(Error::InvalidListLen)
+    }
+
+    /// Reads the element count of an int or long array, returning the length of its payload in
+    /// bytes.
+    fn read_array_len(&mut self, tag: Tag) -> Result<usize> {
+        let width = if tag == Tag::IntArray { 4 } else { 8 };
+        let len = self.read_len()?;
+        len.checked_mul(width).ok_or(Error::SeqLen(len))
+    }
+
+    /// Reads the payload of an int or long array in one block, reordered to little-endian.
+    fn read_array_block(&mut self, tag: Tag) -> Result<Cow<'de, [u8]>> {
+        self.expect_tag(tag)?;
+        let len = self.read_array_len(tag)?;
+        let mut bytes = self.read_bytes(len)?;
+        if !self.little_endian {
+            let width = if tag == Tag::IntArray { 4 } else { 8 };
+            for element in bytes.to_mut().chunks_exact_mut(width) {
+                element.reverse();
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        self.read_str().map(Cow::into_owned)
+    }
+
+    /// Reads a string, borrowing it from the input when the source allows it and the MUTF-8
+    /// bytes are already valid UTF-8, which holds for any string without nulls or characters
+    /// outside the BMP.
+    fn read_str(&mut self) -> Result<Cow<'de, str>> {
+        let len = self.read_u16()?;
+        self.read_str_of_len(len.into())
+    }
+
+    /// Reads a field name, resolving it if it refers to one defined earlier.
+    fn read_name(&mut self) -> Result<Cow<'de, str>> {
+        if self.names.is_none() {
+            return self.read_str();
+        }
+        let len = self.read_u16()?;
+        if len & NAME_REF != 0 {
+            let index = len & !NAME_REF;
+            let names = self.names.as_ref().expect("checked above");
+            return match names.get(usize::from(index)) {
+                Some(name) => Ok(Cow::Owned(name.clone())),
+                None => Err(Error::UnknownName(index)),
+            };
+        }
+        let name = self.read_str_of_len(len.into())?;
+        let names = self.names.as_mut().expect("checked above");
+        if names.len() < usize::from(NAME_REF) {
+            names.push(name.clone().into_owned());
+        }
+        Ok(name)
+    }
+
+    fn read_str_of_len(&mut self, len: usize) -> Result<Cow<'de, str>> {
+        let lenient = self.lenient_mutf8;
+        match self.read_bytes(len)? {
+            Cow::Borrowed(bytes) => match mutf8::mutf8_to_utf8(bytes) {
+                Ok(Cow::Borrowed(utf8)) => match std::str::from_utf8(utf8) {
+                    Ok(string) => Ok(Cow::Borrowed(string)),
+                    Err(_) => self.utf8_string(utf8.to_vec()).map(Cow::Owned),
+                },
+                Ok(Cow::Owned(utf8)) => self.utf8_string(utf8).map(Cow::Owned),
+                Err(_) if lenient => Ok(Cow::Owned(String::from_utf8_lossy(bytes).into_owned())),
+                Err(e) => Err(e.into()),
+            },
+            Cow::Owned(bytes) => {
+                let converted = mutf8::mutf8_to_utf8(&bytes).map(|utf8| match utf8 {
+                    Cow::Borrowed(_) => None,
+                    Cow::Owned(utf8) => Some(utf8),
+                });
+                match converted {
+                    Ok(utf8) => self.utf8_string(utf8.unwrap_or(bytes)).map(Cow::Owned),
+                    Err(_) if lenient => {
+                        Ok(Cow::Owned(String::from_utf8_lossy(&bytes).into_owned()))
+                    }
+                    Err(e) => Err(e.into()),
+                }
+            }
+        }
+    }
+
+    /// Checks the UTF-8 produced from a string's MUTF-8 bytes without copying it again.
+    fn utf8_string(&self, utf8: Vec<u8>) -> Result<String> {
+        match String::from_utf8(utf8) {
+            Ok(string) => Ok(string),
+            Err(e) if self.lenient_mutf8 => Ok(String::from_utf8_lossy(e.as_bytes()).into_owned()),
+            Err(e) => Err(mutf8::error::Error::from(e.utf8_error()).into()),
+        }
+    }
+
+    fn skip_bytes(&mut self, len: usize) -> Result<()> {
+        self.input.skip(len)
+    }
+
+    /// Consumes the payload of a value with the given tag without decoding it.
+    fn skip_value(&mut self, tag: Tag) -> Result<()> {
+        match tag {
+            Tag::End => Ok(()),
+            Tag::Byte => self.skip_bytes(1),
+            Tag::Short => self.skip_bytes(2),
+            Tag::Int | Tag::Float => self.skip_bytes(4),
+            Tag::Long | Tag::Double => self.skip_bytes(8),
+            Tag::ByteArray => {
+                let len = self.read_len()?;
+                self.skip_string_bytes(len)
+            }
+            Tag::String => {
+                let len = self.read_u16()?;
+                self.skip_string_bytes(len.into())
+            }
+            Tag::IntArray | Tag::LongArray => {
+                let len = self.read_array_len(tag)?;
+                self.skip_bytes(len)
+            }
+            Tag::List => {
+                // Elements have no tag or name of their own, so each one is skipped as a bare
+                // payload of the element tag, recursing into nested lists and compounds.
+                let tag = self.read_tag()?;
+                let len = self.read_list_len()?;
+                self.nested(|de| {
+                    for _ in 0..len {
+                        de.skip_value(tag)?;
+                    }
+                    Ok(())
+                })
+            }
+            Tag::Compound => self.nested(|de| {
+                let mut fields = 0;
+                loop {
+                    let tag = de.read_field_tag()?;
+                    if tag == Some(Tag::End) {
+                        break Ok(());
+                    }
+                    fields += 1;
+                    if fields > de.max_fields {
+                        Err(Error::LimitExceeded(fields, de.max_fields))?
+                    }
+                    if de.names.is_some() {
+                        de.read_name()?;
+                    } else {
+                        let len = de.read_u16()?;
+                        de.skip_string_bytes(len.into())?;
+                    }
+                    match (tag, de.custom.take()) {
+                        (Some(tag), _) => de.skip_value(tag)?,
+                        (None, custom) => {
+                            let (_, handler) = custom.expect("a custom tag was just read");
+                            de.read_custom(handler)?;
+                        }
+                    }
+                }
+            }),
+        }
+    }
+
+    fn read_tag(&mut self) -> Result<Tag> {
+        self.read_u8()?.try_into()
+    }
+
+    /// Reads the tag of a compound field. A registered custom tag is kept as the pending
+    /// `custom` field, to be read by its handler after the name, and returned as `None`.
+    fn read_field_tag(&mut self) -> Result<Option<Tag>> {
+        match Tag::try_from(self.read_u8()?) {
+            Err(Error::UnknownTag(tag)) => {
+                let custom = self.custom_tags.iter().find(|&&(t, _)| t == tag);
+                self.custom = Some(*custom.ok_or(Error::UnknownTag(tag))?);
+                Ok(None)
+            }
+            tag => tag.map(Some),
+        }
+    }
+
+    /// Runs the handler of the pending custom field on the input.
+    fn read_custom(&mut self, handler: TagHandler) -> Result<Value> {
+        handler(&mut HandlerInput(&mut self.input))
+    }
+
+    /// Skips the payload of a string or byte array, which must be within the length limit even
+    /// though it isn't read.
+    fn skip_string_bytes(&mut self, len: usize) -> Result<()> {
+        if len > self.max_string_len {
+            Err(Error::LimitExceeded(len, self.max_string_len))?
+        }
+        self.skip_bytes(len)
+    }
+
+    /// Runs `f` to read a list or compound one level deeper.
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= self.max_depth {
+            Err(Error::TooDeep(self.max_depth))?
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Returns the tag of the value about to be read.
+    ///
+    /// Inside a compound or list the tag has already been consumed by the enclosing access. At
+    /// the root, the tag and its (ignored) name are read from the input.
+    fn next_tag(&mut self) -> Result<Tag> {
+        if let Some(variant) = self.inline.take() {
+            Err(Error::UntaggableVariant(variant))?
+        }
+        if let Some((tag, _)) = self.custom.take() {
+            Err(Error::UnknownTag(tag))?
+        }
+        match self.tag.take() {
+            Some(tag) => Ok(tag),
+            None => {
+                let tag = self.read_tag()?;
+                if tag != Tag::End {
+                    self.read_name()?;
+                }
+                self.root = Some(tag);
+                Ok(tag)
+            }
+        }
+    }
+
+    fn expect_tag(&mut self, expected: Tag) -> Result<()> {
+        let found = self.next_tag()?;
+        if found != expected {
+            Err(Error::TagMismatch(expected, found))?
+        }
+        Ok(())
+    }
+
+    /// Reads the terminator after the root, if the root has been read and has one. Neither an
+    /// `End` root nor the root of a bare stream has a terminator after it.
+    pub(crate) fn end_root(&mut self) -> Result<()> {
+        if self.bare {
+            return Ok(());
+        }
+        match self.root.take() {
+            Some(Tag::End) | None => Ok(()),
+            Some(_) => self.end_compound(),
+        }
+    }
+
+    fn end_compound(&mut self) -> Result<()> {
+        match self.read_tag()? {
+            Tag::End => Ok(()),
+            found => Err(Error::TagMismatch(Tag::End, found)),
+        }
+    }
+}
+
+/// Converts an integer read by [`Deserializer::read_integer`] to the target type.
+fn narrow<T: TryFrom<i128>>(value: i128, expected: &'static str) -> Result<T> {
+    T::try_from(value).map_err(|_| {
+        let unexpected = if value < 0 {
+            de::Unexpected::Signed(value as i64)
+        } else {
+            de::Unexpected::Unsigned(value as u64)
+        };
+        de::Error::invalid_value(unexpected, &expected)
+    })
+}
+
+/// Reads the fields of a compound.
+///
+/// Each field starts in tag position, which is the only place an `End` byte terminates the
+/// compound. The value is then read with its tag already consumed, so a payload byte of `0x00`,
+/// such as a `u8` field holding zero, is never mistaken for the terminator.
+struct CompoundAccess<'a, R> {
+    de: &'a mut Deserializer<R>,
+    /// The number of fields read so far, checked against the deserializer's `max_fields`.
+    fields: usize,
+}
+
+impl<'a, R> CompoundAccess<'a, R> {
+    fn new(de: &'a mut Deserializer<R>) -> Self {
+        Self { de, fields: 0 }
+    }
+}
+
+impl<'de, R: Read<'de>> de::MapAccess<'de> for CompoundAccess<'_, R> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let tag = self.de.read_field_tag()?;
+        if tag == Some(Tag::End) {
+            return Ok(None);
+        }
+        self.fields += 1;
+        if self.fields > self.de.max_fields {
+            Err(Error::LimitExceeded(self.fields, self.de.max_fields))?
+        }
+        let name = self.de.read_name()?;
+        self.de.tag = tag;
+        seed.deserialize(KeyDeserializer(name)).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+/// Deserializes a compound field name.
+///
+/// Names are always strings, so integer and `char` keys, which the serializer writes with their
+/// `Display` form, are parsed back out of them. Everything else sees the name as a string.
+struct KeyDeserializer<'de>(Cow<'de, str>);
+
+impl KeyDeserializer<'_> {
+    fn parse<T: std::str::FromStr>(&self, expected: &str) -> Result<T> {
+        self.0.parse().map_err(|_| {
+            de::Error::custom(format_args!(
+                "compound key {:?} cannot be parsed as {expected}",
+                self.0
+            ))
+        })
+    }
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Cow::Borrowed(name) => visitor.visit_borrowed_str(name),
+            Cow::Owned(name) => visitor.visit_string(name),
+        }
+    }
+
+    fn deserialize_i8<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i8(self.parse("i8")?)
+    }
+
+    fn deserialize_i16<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i16(self.parse("i16")?)
+    }
+
+    fn deserialize_i32<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i32(self.parse("i32")?)
+    }
+
+    fn deserialize_i64<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i64(self.parse("i64")?)
+    }
+
+    fn deserialize_i128<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i128(self.parse("i128")?)
+    }
+
+    fn deserialize_u8<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u8(self.parse("u8")?)
+    }
+
+    fn deserialize_u16<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u16(self.parse("u16")?)
+    }
+
+    fn deserialize_u32<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u32(self.parse("u32")?)
+    }
+
+    fn deserialize_u64<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(self.parse("u64")?)
+    }
+
+    fn deserialize_u128<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u128(self.parse("u128")?)
+    }
+
+    fn deserialize_char<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_char(self.parse("a char")?)
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.0 {
+            Cow::Borrowed(key) => {
+                BorrowedStrDeserializer::new(key).deserialize_enum(name, variants, visitor)
+            }
+            Cow::Owned(key) => key
+                .into_deserializer()
+                .deserialize_enum(name, variants, visitor),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool f32 f64 str string bytes byte_buf option unit unit_struct seq tuple tuple_struct
+        map struct identifier ignored_any
+    }
+}
+
+/// An iterator decoding the elements of a list, returned by [`Deserializer::list_iter`].
+///
+/// The iterator stops after the first error, since the input can't be trusted past it.
+pub struct ListIter<'a, R, T> {
+    de: &'a mut Deserializer<R>,
+    tag: Tag,
+    remaining: usize,
+    element: PhantomData<fn() -> T>,
 }
 
-impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
+impl<'de, R: Read<'de>, T: Deserialize<'de>> Iterator for ListIter<'_, R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        self.de.tag = Some(self.tag);
+        let element = T::deserialize(&mut *self.de);
+        if element.is_err() {
+            self.remaining = 0;
+        }
+        Some(element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+/// An iterator decoding a stream of documents, returned by [`Deserializer::into_iter`].
+///
+/// The iterator stops after the first error, since the input can't be trusted past it.
+pub struct DocumentIter<R, T> {
+    de: Deserializer<R>,
+    /// Whether the first document, whose header was read on creation, has been decoded.
+    started: bool,
+    done: bool,
+    document: PhantomData<fn() -> T>,
+}
+
+impl<'de, R: Read<'de>, T: Deserialize<'de>> Iterator for DocumentIter<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.done {
+            return None;
+        }
+        if self.started {
+            match self.de.next_document() {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.done = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.started = true;
+        let document = T::deserialize(&mut self.de).and_then(|value| {
+            self.de.end_root()?;
+            Ok(value)
+        });
+        if document.is_err() {
+            self.done = true;
+        }
+        Some(document)
+    }
+}
+
+/// Reads the elements of a list, which all share `tag`.
+///
+/// Each element is read by the deserializer itself, so strings and byte arrays in a list borrow
+/// from a slice input just like fields do.
+struct ListAccess<'a, R> {
+    de: &'a mut Deserializer<R>,
+    tag: Tag,
+    remaining: usize,
+}
+
+impl<'de, R: Read<'de>> de::SeqAccess<'de> for ListAccess<'_, R> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        self.de.tag = Some(self.tag);
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Reads the elements of a tuple stored as a compound with fields named after their indices.
+struct TupleAccess<'a, R> {
+    de: &'a mut Deserializer<R>,
+    index: usize,
+    len: usize,
+    /// Whether the compound's terminator has been read.
+    ended: bool,
+}
+
+impl<'de, R: Read<'de>> de::SeqAccess<'de> for TupleAccess<'_, R> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>> {
+        if self.index == self.len {
+            return Ok(None);
+        }
+        let tag = self.de.read_tag()?;
+        if tag == Tag::End {
+            self.ended = true;
+            return Ok(None);
+        }
+        let name = self.de.read_name()?;
+        if name != self.index.to_string() {
+            Err(<Error as de::Error>::custom(format_args!(
+                "expected tuple element {}, found field {name}",
+                self.index
+            )))?
+        }
+        self.index += 1;
+        self.de.tag = Some(tag);
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.index)
+    }
+}
+
+struct VariantAccess<'a, R> {
+    de: &'a mut Deserializer<R>,
+    variant: String,
+    /// Whether the variant is one of the enum's own. Any other name can only be accepted by a
+    /// `#[serde(other)]` unit variant, which then skips the payload written by the newer schema.
+    known: bool,
+}
+
+impl<'de, R: Read<'de>> de::EnumAccess<'de> for VariantAccess<'_, R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let variant = IntoDeserializer::<Error>::into_deserializer(self.variant.as_str());
+        let value = seed.deserialize(variant)?;
+        Ok((value, self))
+    }
+}
+
+impl<'de, R: Read<'de>> de::VariantAccess<'de> for VariantAccess<'_, R> {
     type Error = Error;
 
+    fn unit_variant(self) -> Result<()> {
+        match self.de.enum_repr {
+            EnumRepr::External if !self.known => {
+                let tag = self.de.next_tag()?;
+                self.de.skip_value(tag)?;
+                self.de.end_compound()
+            }
+            // Externally tagged unit variants are plain strings and never reach this point.
+            EnumRepr::External => Err(Error::TagMismatch(Tag::String, Tag::Compound)),
+            EnumRepr::Internal(_) if !self.known => self.de.skip_value(Tag::Compound),
+            EnumRepr::Internal(_) => self.de.end_compound(),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        match self.de.enum_repr {
+            EnumRepr::External => {
+                let value = seed.deserialize(&mut *self.de)?;
+                self.de.end_compound()?;
+                Ok(value)
+            }
+            EnumRepr::Internal(_) => {
+                self.de.inline = Some(self.variant);
+                seed.deserialize(&mut *self.de)
+            }
+        }
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        match self.de.enum_repr {
+            EnumRepr::External => {
+                let value = de::Deserializer::deserialize_seq(&mut *self.de, visitor)?;
+                self.de.end_compound()?;
+                Ok(value)
+            }
+            EnumRepr::Internal(_) => Err(Error::UntaggableVariant(self.variant)),
+        }
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.de.enum_repr {
+            EnumRepr::External => {
+                let value = de::Deserializer::deserialize_map(&mut *self.de, visitor)?;
+                self.de.end_compound()?;
+                Ok(value)
+            }
+            EnumRepr::Internal(_) => visitor.visit_map(CompoundAccess::new(self.de)),
+        }
+    }
+}
+
+impl<'de, R: Read<'de>> de::Deserializer<'de> for &mut Deserializer<R> {
+    type Error = Error;
+
+    fn is_human_readable(&self) -> bool {
+        self.human_readable
+    }
+
     fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        if self.inline.is_some() {
+            return self.deserialize_map(visitor);
+        }
+        if let Some((_, handler)) = self.custom.take() {
+            return de::Deserializer::deserialize_any(self.read_custom(handler)?, visitor);
+        }
+        let tag = self.next_tag()?;
+        self.tag = Some(tag);
+        match tag {
+            Tag::End => Err(<Error as de::Error>::invalid_type(
+                de::Unexpected::Other("end tag"),
+                &visitor,
+            )),
+            Tag::Byte => self.deserialize_i8(visitor),
+            Tag::Short => self.deserialize_i16(visitor),
+            Tag::Int => self.deserialize_i32(visitor),
+            Tag::Long => self.deserialize_i64(visitor),
+            Tag::Float => self.deserialize_f32(visitor),
+            Tag::Double => self.deserialize_f64(visitor),
+            Tag::ByteArray => self.deserialize_byte_buf(visitor),
+            Tag::String => self.deserialize_string(visitor),
+            Tag::List => self.deserialize_seq(visitor),
+            Tag::Compound => self.deserialize_map(visitor),
+            Tag::IntArray | Tag::LongArray => {
+                self.tag = None;
+                let len = self.read_len()?;
+                let tag = if tag == Tag::IntArray {
+                    Tag::Int
+                } else {
+                    Tag::Long
+                };
+                visitor.visit_seq(ListAccess {
+                    de: self,
+                    tag,
+                    remaining: len,
+                })
+            }
+        }
     }
 
     fn deserialize_bool<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        self.expect_tag(Tag::Byte)?;
+        match self.read_u8()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            byte if self.strict_bools => Err(Error::InvalidBool(byte)),
+            _ => visitor.visit_bool(true),
+        }
     }
 
     fn deserialize_i8<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        let value = self.read_integer(Tag::Byte, true)?;
+        visitor.visit_i8(narrow(value, "i8")?)
     }
 
     fn deserialize_i16<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        let value = self.read_integer(Tag::Short, true)?;
+        visitor.visit_i16(narrow(value, "i16")?)
     }
 
     fn deserialize_i32<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        let value = self.read_integer(Tag::Int, true)?;
+        visitor.visit_i32(narrow(value, "i32")?)
     }
 
     fn deserialize_i64<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        let value = self.read_integer(Tag::Long, true)?;
+        visitor.visit_i64(narrow(value, "i64")?)
     }
 
     fn deserialize_i128<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        visitor.visit_i128(self.read_u128()? as i128)
     }
 
     fn deserialize_u8<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        let value = self.read_integer(Tag::Byte, false)?;
+        visitor.visit_u8(narrow(value, "u8")?)
     }
 
     fn deserialize_u16<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        let value = self.read_integer(Tag::Short, false)?;
+        visitor.visit_u16(narrow(value, "u16")?)
     }
 
     fn deserialize_u32<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        let value = self.read_integer(Tag::Int, false)?;
+        visitor.visit_u32(narrow(value, "u32")?)
     }
 
     fn deserialize_u64<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        let value = self.read_integer(Tag::Long, false)?;
+        visitor.visit_u64(narrow(value, "u64")?)
     }
 
     fn deserialize_u128<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        visitor.visit_u128(self.read_u128()?)
     }
 
     fn deserialize_f32<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        visitor.visit_f32(match self.read_float(Tag::Float)? {
+            Tag::Double => f64::from_bits(self.read_u64()?) as f32,
+            _ => f32::from_bits(self.read_u32()?),
+        })
     }
 
     fn deserialize_f64<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        visitor.visit_f64(match self.read_float(Tag::Double)? {
+            Tag::Float => f32::from_bits(self.read_u32()?).into(),
+            _ => f64::from_bits(self.read_u64()?),
+        })
     }
 
+    /// A `char` is stored as a one character string, so any string with exactly one code point
+    /// is accepted and anything else is an error.
     fn deserialize_char<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        self.deserialize_str(visitor)
+        self.expect_tag(Tag::String)?;
+        let string = self.read_str()?;
+        let mut chars = string.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(<Error as de::Error>::invalid_value(
+                de::Unexpected::Str(&string),
+                &"a single character",
+            )),
+        }
     }
 
+    /// Strings are only lent to the visitor when they can be borrowed for all of `'de`, which
+    /// needs a slice source. Strings from an [`IoRead`] are always passed by value, so a
+    /// `&str` target fails on them while `String` and `Cow<str>` work.
     fn deserialize_str<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        self.expect_tag(Tag::String)?;
+        match self.read_str()? {
+            Cow::Borrowed(string) => visitor.visit_borrowed_str(string),
+            Cow::Owned(string) => visitor.visit_string(string),
+        }
     }
 
     fn deserialize_string<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
@@ -104,19 +1528,64 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
     }
 
     fn deserialize_bytes<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        self.expect_tag(Tag::ByteArray)?;
+        let len = self.read_len()?;
+        match self.read_bytes(len)? {
+            Cow::Borrowed(bytes) => visitor.visit_borrowed_bytes(bytes),
+            Cow::Owned(bytes) => visitor.visit_byte_buf(bytes),
+        }
     }
 
     fn deserialize_byte_buf<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
         self.deserialize_bytes(visitor)
     }
 
+    /// With [`OptionRepr::Omit`], fields holding `None` are left out of their compound, so any
+    /// value that is present is `Some`. Only at the root can `None` be seen, as a document with
+    /// no root value at all.
     fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        if self.option_repr == OptionRepr::Compound {
+            self.expect_tag(Tag::Compound)?;
+            let tag = self.read_tag()?;
+            if tag == Tag::End {
+                return visitor.visit_none();
+            }
+            let name = self.read_name()?;
+            if name != "Some" {
+                Err(<Error as de::Error>::custom(format_args!(
+                    "expected an option compound holding `Some`, found the field {name:?}"
+                )))?
+            }
+            self.tag = Some(tag);
+            let value = visitor.visit_some(&mut *self)?;
+            self.end_compound()?;
+            return Ok(value);
+        }
+        if self.tag.is_none() && self.inline.is_none() {
+            let tag = self.next_tag()?;
+            if tag == Tag::End {
+                return visitor.visit_none();
+            }
+            self.tag = Some(tag);
+        }
+        visitor.visit_some(self)
     }
 
+    /// A unit is written as an empty compound, and a document holding only an `End` root, which
+    /// has no root value at all, also reads as a unit. A compound with fields fails with
+    /// [`Error::ExpectedUnit`].
     fn deserialize_unit<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        if self.inline.take().is_none() {
+            match self.next_tag()? {
+                Tag::Compound => {}
+                Tag::End => return visitor.visit_unit(),
+                found => Err(Error::TagMismatch(Tag::Compound, found))?,
+            }
+        }
+        if self.read_tag()? != Tag::End {
+            Err(Error::ExpectedUnit)?
+        }
+        visitor.visit_unit()
     }
 
     fn deserialize_unit_struct<V: de::Visitor<'de>>(
@@ -129,31 +1598,74 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
 
     fn deserialize_newtype_struct<V: de::Visitor<'de>>(
         self,
-        _name: &str,
+        name: &str,
         visitor: V,
     ) -> Result<V::Value> {
-        visitor.visit_newtype_struct(self)
+        match array::array_tags(name) {
+            Some((tag, _)) => match self.read_array_block(tag)? {
+                Cow::Borrowed(bytes) => visitor.visit_borrowed_bytes(bytes),
+                Cow::Owned(bytes) => visitor.visit_byte_buf(bytes),
+            },
+            None => visitor.visit_newtype_struct(self),
+        }
     }
 
     fn deserialize_seq<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        self.expect_tag(Tag::List)?;
+        let tag = self.read_tag()?;
+        let remaining = self.read_list_len()?;
+        self.nested(|de| visitor.visit_seq(ListAccess { de, tag, remaining }))
     }
 
-    fn deserialize_tuple<V: de::Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
-        todo!()
+    /// The list must hold exactly `len` elements. Any others couldn't be handed to the visitor
+    /// and would be left unread in the input.
+    fn deserialize_tuple<V: de::Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        match self.tuple_repr {
+            TupleRepr::List => {
+                self.expect_tag(Tag::List)?;
+                let tag = self.read_tag()?;
+                let remaining = self.read_list_len()?;
+                if remaining != len {
+                    Err(<Error as de::Error>::invalid_length(
+                        remaining,
+                        &&*format!("a list of {len} elements"),
+                    ))?
+                }
+                self.nested(|de| visitor.visit_seq(ListAccess { de, tag, remaining }))
+            }
+            TupleRepr::Compound => {
+                self.expect_tag(Tag::Compound)?;
+                self.nested(|de| {
+                    let mut access = TupleAccess {
+                        de,
+                        index: 0,
+                        len,
+                        ended: false,
+                    };
+                    let value = visitor.visit_seq(&mut access)?;
+                    if !access.ended {
+                        access.de.end_compound()?;
+                    }
+                    Ok(value)
+                })
+            }
+        }
     }
 
     fn deserialize_tuple_struct<V: de::Visitor<'de>>(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
         visitor: V,
     ) -> Result<V::Value> {
-        todo!()
+        self.deserialize_tuple(len, visitor)
     }
 
     fn deserialize_map<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        if self.inline.take().is_none() {
+            self.expect_tag(Tag::Compound)?;
+        }
+        self.nested(|de| visitor.visit_map(CompoundAccess::new(de)))
     }
 
     fn deserialize_struct<V: de::Visitor<'de>>(
@@ -162,23 +1674,64 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
         _fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value> {
-        todo!()
+        self.deserialize_map(visitor)
     }
 
     fn deserialize_enum<V: de::Visitor<'de>>(
         self,
-        name: &'static str,
+        _name: &'static str,
         variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value> {
-        todo!()
+        let known = |variant: &str| variants.contains(&variant);
+        match self.enum_repr {
+            EnumRepr::External => match self.next_tag()? {
+                Tag::String => visitor.visit_enum(self.read_string()?.into_deserializer()),
+                Tag::Compound => self.nested(|de| {
+                    let tag = de.read_tag()?;
+                    if tag == Tag::End {
+                        Err(<Error as de::Error>::invalid_length(
+                            0,
+                            &"a single variant field",
+                        ))?
+                    }
+                    let variant = de.read_name()?.into_owned();
+                    de.tag = Some(tag);
+                    let known = known(&variant);
+                    visitor.visit_enum(VariantAccess { de, variant, known })
+                }),
+                found => Err(Error::TagMismatch(Tag::Compound, found)),
+            },
+            EnumRepr::Internal(key) => {
+                self.expect_tag(Tag::Compound)?;
+                self.nested(|de| {
+                    if de.read_tag()? != Tag::String || de.read_name()? != key {
+                        Err(Error::MissingEnumTag(key))?
+                    }
+                    let variant = de.read_string()?;
+                    let known = known(&variant);
+                    visitor.visit_enum(VariantAccess { de, variant, known })
+                })
+            }
+        }
     }
 
+    /// Field and variant names at value position are stored as strings, like
+    /// [`deserialize_str`](de::Deserializer::deserialize_str) expects.
     fn deserialize_identifier<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        self.deserialize_str(visitor)
     }
 
+    /// Skips the value without decoding it, while still walking every nested tag and length. At
+    /// the root this reads the root tag and name first, so decoding a document as
+    /// [`IgnoredAny`](de::IgnoredAny) checks its structure cheaply, whatever the root's tag.
     fn deserialize_ignored_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        todo!()
+        if let Some((_, handler)) = self.custom.take() {
+            self.read_custom(handler)?;
+            return visitor.visit_unit();
+        }
+        let tag = self.next_tag()?;
+        self.skip_value(tag)?;
+        visitor.visit_unit()
     }
 }
@@ -0,0 +1,69 @@
+//! Tools for inspecting encoded documents while debugging.
+
+use std::collections::BTreeSet;
+
+use crate::error::Result;
+use crate::{from_slice, Value};
+
+/// A value that differs between the two documents given to [`diff`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldDiff {
+    /// The field names and list indices leading from the root to the value, in the form taken by
+    /// [`patch_field`](crate::patch_field). Empty when the roots themselves differ.
+    pub path: Vec<String>,
+    /// The value in the first document, or `None` if it is missing there.
+    pub old: Option<Value>,
+    /// The value in the second document, or `None` if it is missing there.
+    pub new: Option<Value>,
+}
+
+/// Decodes both documents and lists where they differ, in the order the fields sort in.
+///
+/// Compounds are compared field by field and lists element by element, so only the innermost
+/// differing values are reported. Values with different tags are reported whole. Floats compare
+/// by their bits, so a NaN matches an identical NaN and `-0.0` differs from `0.0`.
+pub fn diff(a: &[u8], b: &[u8]) -> Result<Vec<FieldDiff>> {
+    let a = from_slice::<Value>(a)?;
+    let b = from_slice::<Value>(b)?;
+    let mut diffs = Vec::new();
+    diff_values(&mut Vec::new(), Some(&a), Some(&b), &mut diffs);
+    Ok(diffs)
+}
+
+fn diff_values(
+    path: &mut Vec<String>,
+    old: Option<&Value>,
+    new: Option<&Value>,
+    diffs: &mut Vec<FieldDiff>,
+) {
+    match (old, new) {
+        (Some(Value::Compound(old)), Some(Value::Compound(new))) => {
+            let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                path.push(key.clone());
+                diff_values(path, old.get(key), new.get(key), diffs);
+                path.pop();
+            }
+        }
+        (Some(Value::List(old)), Some(Value::List(new))) => {
+            for i in 0..old.len().max(new.len()) {
+                path.push(i.to_string());
+                diff_values(path, old.get(i), new.get(i), diffs);
+                path.pop();
+            }
+        }
+        (Some(Value::Float(a)), Some(Value::Float(b))) if a.to_bits() == b.to_bits() => {}
+        (Some(Value::Double(a)), Some(Value::Double(b))) if a.to_bits() == b.to_bits() => {}
+        (Some(Value::Float(_) | Value::Double(_)), Some(_)) => push(path, old, new, diffs),
+        _ if old != new => push(path, old, new, diffs),
+        _ => {}
+    }
+}
+
+fn push(path: &[String], old: Option<&Value>, new: Option<&Value>, diffs: &mut Vec<FieldDiff>) {
+    diffs.push(FieldDiff {
+        path: path.to_vec(),
+        old: old.cloned(),
+        new: new.cloned(),
+    });
+}
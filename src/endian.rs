@@ -0,0 +1,179 @@
+use crate::de::{read_header, DEFAULT_MAX_DEPTH, NAME_REF};
+use crate::error::{Error, Result};
+use crate::Tag;
+
+/// Converts the encoded document in `buf` between little- and big-endian in place, flipping
+/// the header's `0x80` flag and byte-swapping every number and length prefix.
+///
+/// The bodies of strings and byte arrays are left as they are, since their bytes have no
+/// order to swap. Lists and compounds nested deeper than the
+/// [`Deserializer`](crate::Deserializer)'s default depth limit fail with [`Error::TooDeep`]. On
+/// error `buf` is left partly converted and should be discarded.
+pub fn swap_endianness(buf: &mut [u8]) -> Result<()> {
+    let header = read_header(&buf[..])?;
+    buf[6] ^= 0x80;
+    let mut swapper = Swapper {
+        buf,
+        pos: 7,
+        little_endian: !header.is_big_endian(),
+        interned: header.has_interned_names(),
+        compact_lists: header.has_compact_lists(),
+        depth: 0,
+    };
+    let tag = swapper.tag()?;
+    if tag != Tag::End {
+        swapper.name()?;
+        swapper.value(tag)?;
+    }
+    Ok(())
+}
+
+/// Walks a document, swapping each number once it has been read in the original order.
+struct Swapper<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+    little_endian: bool,
+    /// Whether a name may be a reference to an earlier one instead of a string.
+    interned: bool,
+    /// Whether list lengths are LEB128, which has no byte order.
+    compact_lists: bool,
+    /// The number of lists and compounds currently being walked.
+    depth: usize,
+}
+
+impl Swapper<'_> {
+    /// Reverses the next `N` bytes, returning them in their original order.
+    fn swap<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let end = self.pos + N;
+        let bytes: &mut [u8; N] = self
+            .buf
+            .get_mut(self.pos..end)
+            .ok_or(Error::Eof)?
+            .try_into()
+            .expect("the range is N bytes long");
+        let original = *bytes;
+        bytes.reverse();
+        self.pos = end;
+        Ok(original)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<()> {
+        if self.buf.len() - self.pos < len {
+            Err(Error::Eof)?
+        }
+        self.pos += len;
+        Ok(())
+    }
+
+    fn tag(&mut self) -> Result<Tag> {
+        let tag = *self.buf.get(self.pos).ok_or(Error::Eof)?;
+        self.pos += 1;
+        Tag::try_from(tag)
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        let bytes = self.swap()?;
+        let len = if self.little_endian {
+            i32::from_le_bytes(bytes)
+        } else {
+            i32::from_be_bytes(bytes)
+        };
+        usize::try_from(len).map_err(|_| Error::NegativeLen(len))
+    }
+
+    fn list_len(&mut self) -> Result<usize> {
+        if !self.compact_lists {
+            return self.len();
+        }
+        let mut len = 0usize;
+        for i in 0..5 {
+            let byte = *self.buf.get(self.pos).ok_or(Error::Eof)?;
+            self.pos += 1;
+            len |= usize::from(byte & 0x7f) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(len);
+            }
+        }
+        Err(Error::InvalidListLen)
+    }
+
+    fn string(&mut self) -> Result<()> {
+        let len = self.u16()?;
+        self.skip(len.into())
+    }
+
+    fn name(&mut self) -> Result<()> {
+        let len = self.u16()?;
+        if self.interned && len & NAME_REF != 0 {
+            return Ok(());
+        }
+        self.skip(len.into())
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.swap()?;
+        Ok(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    /// Walks the payload of a list or compound one level deeper, bounding the recursion.
+    fn nested(&mut self, f: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        if self.depth >= DEFAULT_MAX_DEPTH {
+            Err(Error::TooDeep(DEFAULT_MAX_DEPTH))?
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn value(&mut self, tag: Tag) -> Result<()> {
+        match tag {
+            Tag::End => {}
+            Tag::Byte => self.skip(1)?,
+            Tag::Short => {
+                self.swap::<2>()?;
+            }
+            Tag::Int | Tag::Float => {
+                self.swap::<4>()?;
+            }
+            Tag::Long | Tag::Double => {
+                self.swap::<8>()?;
+            }
+            Tag::ByteArray => {
+                let len = self.len()?;
+                self.skip(len)?;
+            }
+            Tag::String => self.string()?,
+            Tag::List => self.nested(|swapper| {
+                let element = swapper.tag()?;
+                for _ in 0..swapper.list_len()? {
+                    swapper.value(element)?;
+                }
+                Ok(())
+            })?,
+            Tag::Compound => self.nested(|swapper| loop {
+                let field = swapper.tag()?;
+                if field == Tag::End {
+                    break Ok(());
+                }
+                swapper.name()?;
+                swapper.value(field)?;
+            })?,
+            Tag::IntArray => {
+                for _ in 0..self.len()? {
+                    self.swap::<4>()?;
+                }
+            }
+            Tag::LongArray => {
+                for _ in 0..self.len()? {
+                    self.swap::<8>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -3,6 +3,9 @@ use std::fmt::Display;
 use serde::{de, ser};
 use thiserror::Error;
 
+use crate::tag::DisplayTag;
+use crate::Tag;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, Error)]
@@ -17,12 +20,66 @@ pub enum Error {
     StrLen(usize),
     #[error("sequence length of {0} does not fit into a i32")]
     SeqLen(usize),
+    #[error("byte array length of {0} does not fit into a i32")]
+    BytesLen(usize),
+    #[error("sequence declared {0} elements but {1} were written")]
+    SeqLenMismatch(usize, usize),
     #[error("{0}")]
     Mutf8(#[from] mutf8::error::Error),
     #[error("did not detect a valid ShadeNBT header")]
     InvalidHeader,
+    #[error("input ended after {0} bytes of the ShadeNBT header")]
+    TruncatedHeader(usize),
     #[error("field name is unset")]
     FieldInfoUnset,
+    #[error("invalid tag {}", DisplayTag(*.0))]
+    UnknownTag(u8),
+    #[error("expected tag {0}, found tag {1}")]
+    TagMismatch(Tag, Tag),
+    #[error("length of {0} is negative")]
+    NegativeLen(i32),
+    #[error("length of {0} exceeds the configured limit of {1}")]
+    LimitExceeded(usize, usize),
+    #[error("lists and compounds are nested more than {0} deep")]
+    TooDeep(usize),
+    #[error("unexpected bytes after the end of the document")]
+    TrailingBytes,
+    #[error("variant {0} cannot be internally tagged")]
+    UntaggableVariant(String),
+    #[error("expected the enum tag {0} as the first field")]
+    MissingEnumTag(&'static str),
+    #[error("output buffer is full")]
+    BufferFull,
+    #[error("compound keys must be strings")]
+    KeyMustBeString,
+    #[error("None cannot be written as a list element")]
+    NoneInList,
+    #[error("no value at {0}")]
+    FieldNotFound(String),
+    #[error("big-endian documents cannot be patched")]
+    BigEndianPatch,
+    #[error("documents with interned names cannot be patched")]
+    InternedPatch,
+    #[error("documents with compact list lengths cannot be patched")]
+    CompactPatch,
+    #[error("list length is not a LEB128 integer of at most five bytes up to i32::MAX")]
+    InvalidListLen,
+    #[error("expected a unit, stored as an empty compound, but the compound has fields")]
+    ExpectedUnit,
+    #[error("byte {0} is not a valid bool, which must be 0 or 1")]
+    InvalidBool(u8),
+    #[error("map key `{0}` is written twice, such as by a flattened map repeating a named field")]
+    DuplicateKey(String),
+    #[error("map keys cannot be sorted while names are interned")]
+    InternedSort,
+    #[error("reference to interned name {0}, which has not been defined")]
+    UnknownName(u16),
+    #[error("the checksum trailer is missing or does not match the document length")]
+    MissingTrailer,
+    #[error("checksum mismatch: the trailer records {0:#010x}, the document hashes to {1:#010x}")]
+    ChecksumMismatch(u32, u32),
+    #[error("document length of {0} does not fit into the u32 of the checksum trailer")]
+    DocumentTooLong(usize),
 }
 
 impl ser::Error for Error {
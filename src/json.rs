@@ -0,0 +1,169 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer};
+
+use crate::error::Error;
+use crate::Deserializer;
+
+/// Converts a stream of documents from `reader` into JSON Lines on `writer`, one JSON value per
+/// line, and returns the number of documents converted. The stream ends cleanly when `reader`
+/// ends where the next header would start; input that ends anywhere else is an error.
+///
+/// Values are passed straight from the reader to the writer, so no document is ever held in
+/// memory as a whole. JSON has fewer types than ShadeNBT, so the mapping loses information:
+///
+/// - Every integer becomes a plain JSON number, so the width of its tag is lost. Longs beyond
+///   2<sup>53</sup> are written exactly, but many JSON readers round them on the way in.
+/// - NaN and infinite floats have no JSON form and become `null`.
+/// - Byte, int and long arrays become arrays of numbers, indistinguishable from lists.
+/// - A document whose root is the bare `End` tag becomes `null`.
+pub fn transcode_to_jsonl<R: io::Read, W: io::Write>(
+    reader: R,
+    mut writer: W,
+) -> crate::Result<usize> {
+    let mut de = match Deserializer::from_reader(reader) {
+        Ok(de) => de,
+        Err(Error::Eof) => return Ok(0),
+        Err(e) => Err(e)?,
+    };
+    let mut count = 0;
+    loop {
+        Transcoder::new(&mut de)
+            .serialize(&mut serde_json::Serializer::new(&mut writer))
+            .map_err(|e| match e.io_error_kind() {
+                Some(_) => Error::Io(e.into()),
+                None => Error::Message(e.to_string()),
+            })?;
+        de.end_root()?;
+        writer.write_all(b"\n")?;
+        count += 1;
+        if !de.next_document()? {
+            break;
+        }
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Serializes whatever the deserializer inside it produces, as it produces it.
+struct Transcoder<D>(RefCell<Option<D>>);
+
+impl<D> Transcoder<D> {
+    fn new(deserializer: D) -> Self {
+        Self(RefCell::new(Some(deserializer)))
+    }
+}
+
+impl<'de, D: de::Deserializer<'de>> Serialize for Transcoder<D> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let deserializer = self
+            .0
+            .borrow_mut()
+            .take()
+            .expect("a value is transcoded once");
+        deserializer
+            .deserialize_any(Forward(serializer))
+            .map_err(ser::Error::custom)
+    }
+}
+
+/// Passes each value it visits on to the serializer.
+struct Forward<S>(S);
+
+macro_rules! forward {
+    ($($visit:ident($ty:ty) => $serialize:ident,)*) => {$(
+        fn $visit<E: de::Error>(self, v: $ty) -> Result<S::Ok, E> {
+            self.0.$serialize(v).map_err(de::Error::custom)
+        }
+    )*};
+}
+
+impl<'de, S: Serializer> Visitor<'de> for Forward<S> {
+    type Value = S::Ok;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a ShadeNBT value")
+    }
+
+    forward! {
+        visit_bool(bool) => serialize_bool,
+        visit_i8(i8) => serialize_i8,
+        visit_i16(i16) => serialize_i16,
+        visit_i32(i32) => serialize_i32,
+        visit_i64(i64) => serialize_i64,
+        visit_u8(u8) => serialize_u8,
+        visit_u16(u16) => serialize_u16,
+        visit_u32(u32) => serialize_u32,
+        visit_u64(u64) => serialize_u64,
+        visit_f32(f32) => serialize_f32,
+        visit_f64(f64) => serialize_f64,
+        visit_str(&str) => serialize_str,
+        visit_bytes(&[u8]) => serialize_bytes,
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<S::Ok, E> {
+        self.0.serialize_unit().map_err(de::Error::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<S::Ok, A::Error> {
+        let mut out = self
+            .0
+            .serialize_seq(seq.size_hint())
+            .map_err(de::Error::custom)?;
+        while seq.next_element_seed(Element(&mut out))?.is_some() {}
+        out.end().map_err(de::Error::custom)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<S::Ok, A::Error> {
+        let mut out = self
+            .0
+            .serialize_map(map.size_hint())
+            .map_err(de::Error::custom)?;
+        while map.next_key_seed(Key(&mut out))?.is_some() {
+            map.next_value_seed(Entry(&mut out))?;
+        }
+        out.end().map_err(de::Error::custom)
+    }
+}
+
+/// Transcodes a list element.
+struct Element<'a, S>(&'a mut S);
+
+impl<'de, S: SerializeSeq> DeserializeSeed<'de> for Element<'_, S> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        self.0
+            .serialize_element(&Transcoder::new(deserializer))
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Transcodes a field name.
+struct Key<'a, S>(&'a mut S);
+
+impl<'de, S: SerializeMap> DeserializeSeed<'de> for Key<'_, S> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        self.0
+            .serialize_key(&Transcoder::new(deserializer))
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Transcodes a field's value.
+struct Entry<'a, S>(&'a mut S);
+
+impl<'de, S: SerializeMap> DeserializeSeed<'de> for Entry<'_, S> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        self.0
+            .serialize_value(&Transcoder::new(deserializer))
+            .map_err(de::Error::custom)
+    }
+}
@@ -1,16 +1,82 @@
+#[cfg(feature = "arena")]
+mod arena;
+mod array;
+mod checksum;
+pub mod codec;
+#[cfg(test)]
+mod conformance;
 mod de;
+pub mod debug;
+mod endian;
 mod error;
+#[cfg(feature = "json")]
+mod json;
+mod option_list;
+mod patch;
+mod read;
+mod repr;
 mod ser;
+mod snbt;
+#[cfg(feature = "tokio")]
+mod stream;
+mod tag;
+mod value;
 
-pub use de::{from_reader, from_slice, Deserializer};
+#[cfg(feature = "arena")]
+pub use arena::ArenaValue;
+pub use array::{IntArray, LongArray};
+pub use checksum::{from_slice_checked, to_vec_checked};
+#[cfg(feature = "mmap")]
+pub use de::from_mmap;
+pub use de::{
+    from_reader, from_reader_counted, from_slice, get_path, read_header, root_keys, validate,
+    Deserializer, DocumentIter, HeaderInfo, ListIter, PathSeg, TagHandler,
+};
+pub use endian::swap_endianness;
 pub use error::{Error, Result};
-pub use ser::{to_vec, to_writer, Serializer};
+#[cfg(feature = "json")]
+pub use json::transcode_to_jsonl;
+pub use option_list::OptionList;
+pub use patch::{merge_from_slice, patch_field};
+pub use read::{IoRead, Read, SliceRead};
+pub use repr::{EnumRepr, OptionRepr, TupleRepr};
+pub use ser::{dry_run, tee, to_buf, to_vec, to_writer, SerializeStats, Serializer, Tee};
+#[cfg(feature = "tokio")]
+pub use stream::{from_stream, DocumentStream};
+pub use tag::Tag;
+pub use value::{CompoundBuilder, Value, ValueRef};
 
 #[cfg(test)]
 mod test {
-    use serde::Serialize;
+    use std::borrow::Cow;
+    use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+    use std::io::{BufWriter, Cursor};
+    use std::marker::PhantomData;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::num::{NonZeroI64, NonZeroU32};
+    use std::time::Duration;
+
+    use serde::de::{DeserializeOwned, IgnoredAny};
+    use serde::ser::SerializeMap;
+    use serde::{Deserialize, Serialize};
+    use serde_bytes::{ByteBuf, Bytes};
+    use serde_with::{DurationSeconds, IfIsHumanReadable};
+
+    use crate::{
+        debug, dry_run, from_reader, from_reader_counted, from_slice, from_slice_checked, get_path,
+        merge_from_slice, patch_field, read_header, root_keys, swap_endianness, tee, to_buf,
+        to_vec, to_vec_checked, to_writer, validate, Deserializer, EnumRepr, Error, HeaderInfo,
+        IntArray, IoRead, LongArray, OptionList, OptionRepr, PathSeg, Serializer, SliceRead, Tag,
+        TupleRepr, Value, ValueRef,
+    };
+
+    const HEADER: [u8; 7] = [0xAD, 0x4E, 0x42, 0x54, 0x00, 0x05, 0x00];
 
-    use crate::to_vec;
+    fn document(body: &[u8]) -> Vec<u8> {
+        let mut doc = HEADER.to_vec();
+        doc.extend_from_slice(body);
+        doc
+    }
 
     #[derive(Serialize)]
     struct Test {}
@@ -18,6 +84,3847 @@ mod test {
     #[test]
     fn empty_compound_ser() {
         let result = to_vec(&Test {});
-        assert_eq!(result.unwrap(), [0xAD, 0x4E, 0x42, 0x54, 0x00, 0x05, 0x80, 0x0a, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(
+            result.unwrap(),
+            [0xAD, 0x4E, 0x42, 0x54, 0x00, 0x05, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn max_string_len() {
+        let doc = document(&[
+            0x08, 0x00, 0x00, 0x05, 0x00, b'h', b'e', b'l', b'l', b'o', 0x00,
+        ]);
+
+        let mut de = Deserializer::from_slice(&doc).unwrap();
+        de.set_max_string_len(5);
+        assert_eq!(String::deserialize(&mut de).unwrap(), "hello");
+
+        let mut de = Deserializer::from_slice(&doc).unwrap();
+        de.set_max_string_len(4);
+        let err = String::deserialize(&mut de).unwrap_err();
+        assert!(matches!(err, Error::LimitExceeded(5, 4)), "{err}");
+    }
+
+    #[test]
+    fn max_bytes_len() {
+        let doc = document(&[0x07, 0x00, 0x00, 0xff, 0xff, 0xff, 0x7f]);
+
+        let mut de = Deserializer::from_slice(&doc).unwrap();
+        de.set_max_string_len(1024);
+        let err = ByteBuf::deserialize(&mut de).unwrap_err();
+        assert!(
+            matches!(err, Error::LimitExceeded(0x7fff_ffff, 1024)),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn nested_compound_terminators() {
+        #[derive(Serialize)]
+        struct Outer {
+            middle: Middle,
+        }
+
+        #[derive(Serialize)]
+        struct Middle {
+            inner: Inner,
+        }
+
+        #[derive(Serialize)]
+        struct Inner {
+            x: u8,
+        }
+
+        let value = Outer {
+            middle: Middle {
+                inner: Inner { x: 7 },
+            },
+        };
+        assert_eq!(
+            to_vec(&value).unwrap(),
+            document(&[
+                0x0a, 0x00, 0x00, // root
+                0x0a, 0x06, 0x00, b'm', b'i', b'd', b'd', b'l', b'e', // middle
+                0x0a, 0x05, 0x00, b'i', b'n', b'n', b'e', b'r', // inner
+                0x01, 0x01, 0x00, b'x', 0x07, // x
+                0x00, // end of inner
+                0x00, // end of middle
+                0x00, // end of root
+                0x00, // end of document
+            ])
+        );
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Size {
+        w: u16,
+        h: u16,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Point,
+        Circle { radius: f32 },
+        Rect(Size),
+    }
+
+    fn to_vec_repr<T: Serialize>(value: &T, repr: EnumRepr) -> crate::Result<Vec<u8>> {
+        let mut ser = Serializer::new(Vec::new())?;
+        ser.set_enum_repr(repr);
+        value.serialize(&mut ser)?;
+        ser.finish()
+    }
+
+    fn from_slice_repr<T: DeserializeOwned>(bytes: &[u8], repr: EnumRepr) -> crate::Result<T> {
+        let mut de = Deserializer::from_slice(bytes)?;
+        de.set_enum_repr(repr);
+        T::deserialize(&mut de)
+    }
+
+    #[test]
+    fn externally_tagged_round_trip() {
+        let shapes = [
+            Shape::Point,
+            Shape::Circle { radius: 1.5 },
+            Shape::Rect(Size { w: 3, h: 4 }),
+        ];
+        for shape in shapes {
+            let bytes = to_vec(&shape).unwrap();
+            assert_eq!(from_slice::<Shape>(&bytes).unwrap(), shape);
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Command {
+        Move(i16, i16),
+        Draw(Shape),
+        Stack(Vec<Shape>),
+        Replace { old: Shape, new: Shape },
+    }
+
+    #[test]
+    fn externally_tagged_payloads() {
+        let commands = [
+            Command::Move(-1, 2),
+            Command::Draw(Shape::Point),
+            Command::Draw(Shape::Rect(Size { w: 3, h: 4 })),
+            Command::Stack(vec![
+                Shape::Circle { radius: 1.5 },
+                Shape::Rect(Size { w: 1, h: 2 }),
+            ]),
+            Command::Replace {
+                old: Shape::Point,
+                new: Shape::Circle { radius: 2.0 },
+            },
+        ];
+        for command in commands {
+            let bytes = to_vec(&command).unwrap();
+            assert_eq!(from_slice::<Command>(&bytes).unwrap(), command);
+        }
+
+        assert_eq!(
+            to_vec(&Command::Move(-1, 2)).unwrap(),
+            document(&[
+                0x0a, 0x00, 0x00, // root
+                0x09, 0x04, 0x00, b'M', b'o', b'v', b'e', 0x02, 0x02, 0x00, 0x00,
+                0x00, // Move
+                0xff, 0xff, 0x02, 0x00, // elements
+                0x00, // end of root
+                0x00, // end of document
+            ])
+        );
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum OldShape {
+        Point,
+        Circle {
+            radius: f32,
+        },
+        #[serde(other)]
+        Other,
+    }
+
+    #[test]
+    fn unknown_variants() {
+        let shapes = [
+            Shape::Point,
+            Shape::Circle { radius: 1.5 },
+            Shape::Rect(Size { w: 3, h: 4 }),
+        ];
+        let old = [
+            OldShape::Point,
+            OldShape::Circle { radius: 1.5 },
+            OldShape::Other,
+        ];
+        for repr in [EnumRepr::External, EnumRepr::Internal("type")] {
+            for (shape, old) in shapes.iter().zip(&old) {
+                let bytes = to_vec_repr(shape, repr).unwrap();
+                assert_eq!(&from_slice_repr::<OldShape>(&bytes, repr).unwrap(), old);
+            }
+        }
+
+        // The payload of an unknown variant is skipped, so the rest of the document still
+        // decodes.
+        #[derive(Serialize)]
+        struct Pair {
+            first: Shape,
+            second: Shape,
+        }
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct OldPair {
+            first: OldShape,
+            second: OldShape,
+        }
+        let pair = Pair {
+            first: Shape::Rect(Size { w: 3, h: 4 }),
+            second: Shape::Point,
+        };
+        assert_eq!(
+            from_slice::<OldPair>(&to_vec(&pair).unwrap()).unwrap(),
+            OldPair {
+                first: OldShape::Other,
+                second: OldShape::Point,
+            }
+        );
+
+        // A known unit variant with a payload is still malformed.
+        let bytes = document(&[
+            0x0a, 0x00, 0x00, // root
+            0x01, 0x05, 0x00, b'P', b'o', b'i', b'n', b't', 0x01, // Point
+            0x00, // end of root
+            0x00, // end of document
+        ]);
+        assert!(matches!(
+            from_slice::<OldShape>(&bytes),
+            Err(Error::TagMismatch(Tag::String, Tag::Compound))
+        ));
+
+        // Without a catch-all, an unknown variant is an error of its own.
+        #[derive(Deserialize, Debug)]
+        enum Strict {
+            Point,
+        }
+        let bytes = to_vec(&Shape::Rect(Size { w: 3, h: 4 })).unwrap();
+        let err = from_slice::<Strict>(&bytes).unwrap_err();
+        assert_eq!(err.to_string(), "unknown variant `Rect`, expected `Point`");
+    }
+
+    #[test]
+    fn internally_tagged_round_trip() {
+        let repr = EnumRepr::Internal("type");
+        let shapes = [
+            Shape::Point,
+            Shape::Circle { radius: 1.5 },
+            Shape::Rect(Size { w: 3, h: 4 }),
+        ];
+        for shape in shapes {
+            let bytes = to_vec_repr(&shape, repr).unwrap();
+            assert_eq!(from_slice_repr::<Shape>(&bytes, repr).unwrap(), shape);
+        }
+    }
+
+    #[test]
+    fn internally_tagged_layout() {
+        let bytes = to_vec_repr(
+            &Shape::Rect(Size { w: 3, h: 4 }),
+            EnumRepr::Internal("type"),
+        );
+        assert_eq!(
+            bytes.unwrap(),
+            document(&[
+                0x0a, 0x00, 0x00, // root
+                0x08, 0x04, 0x00, b't', b'y', b'p', b'e', 0x04, 0x00, b'R', b'e', b'c', b't', 0x02,
+                0x01, 0x00, b'w', 0x03, 0x00, // w
+                0x02, 0x01, 0x00, b'h', 0x04, 0x00, // h
+                0x00, // end of root
+                0x00, // end of document
+            ])
+        );
+    }
+
+    #[test]
+    fn internally_tagged_newtype_scalar() {
+        #[derive(Serialize, Deserialize, Debug)]
+        enum Wrapper {
+            Number(u32),
+        }
+
+        let repr = EnumRepr::Internal("type");
+        let err = to_vec_repr(&Wrapper::Number(1), repr).unwrap_err();
+        assert!(
+            matches!(err, Error::UntaggableVariant(ref v) if v == "Number"),
+            "{err}"
+        );
+
+        let bytes = document(&[
+            0x0a, 0x00, 0x00, // root
+            0x08, 0x04, 0x00, b't', b'y', b'p', b'e', 0x06, 0x00, b'N', b'u', b'm', b'b', b'e',
+            b'r', 0x03, 0x01, 0x00, b'x', 0x01, 0x00, 0x00, 0x00, // x
+            0x00, // end of root
+            0x00, // end of document
+        ]);
+        let err = from_slice_repr::<Wrapper>(&bytes, repr).unwrap_err();
+        assert!(
+            matches!(err, Error::UntaggableVariant(ref v) if v == "Number"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn list_root_keys() {
+        #[derive(Serialize)]
+        struct Position {
+            x: f64,
+            y: f64,
+            z: f64,
+        }
+
+        #[derive(Serialize)]
+        struct Player {
+            id: u8,
+            name: String,
+            pos: Position,
+            inventory: Vec<Shape>,
+            data: ByteBuf,
+            score: i64,
+        }
+
+        let player = Player {
+            id: 1,
+            name: "Steve".to_string(),
+            pos: Position {
+                x: 1.0,
+                y: 64.0,
+                z: -3.5,
+            },
+            inventory: vec![Shape::Circle { radius: 2.0 }],
+            data: ByteBuf::from(vec![1, 2, 3]),
+            score: -12,
+        };
+        let bytes = to_vec(&player).unwrap();
+        assert_eq!(
+            root_keys(&bytes[..]).unwrap(),
+            ["id", "name", "pos", "inventory", "data", "score"]
+        );
+    }
+
+    #[test]
+    fn tag_error_display() {
+        let err = Error::TagMismatch(Tag::Compound, Tag::String);
+        assert_eq!(
+            err.to_string(),
+            "expected tag Compound (0x0a), found tag String (0x08)"
+        );
+        assert_eq!(
+            Error::UnknownTag(0x01).to_string(),
+            "invalid tag Byte (0x01)"
+        );
+        assert_eq!(
+            Error::UnknownTag(0x42).to_string(),
+            "invalid tag Unknown (0x42)"
+        );
+    }
+
+    #[test]
+    fn list_from_iter() {
+        let mut ser = Serializer::new(Vec::new()).unwrap();
+        ser.serialize_list_from_iter("values", 0..1_000_000i32)
+            .unwrap();
+        let bytes = ser.finish().unwrap();
+        let values = from_slice::<Vec<i32>>(&bytes).unwrap();
+        assert_eq!(values.len(), 1_000_000);
+        assert!(values.iter().copied().eq(0..1_000_000));
+    }
+
+    #[test]
+    fn list_from_iter_seekable() {
+        let mut ser = Serializer::new(Cursor::new(Vec::new())).unwrap();
+        ser.serialize_list_from_iter_seekable("values", 0..1_000_000i32)
+            .unwrap();
+        let bytes = ser.finish().unwrap().into_inner();
+        let values = from_slice::<Vec<i32>>(&bytes).unwrap();
+        assert_eq!(values.len(), 1_000_000);
+        assert!(values.iter().copied().eq(0..1_000_000));
+    }
+
+    #[test]
+    fn empty_list_from_iter() {
+        let expected = document(&[
+            0x09, 0x00, 0x00, // root
+            0x00, 0x00, 0x00, 0x00, 0x00, // no elements
+            0x00, // end of document
+        ]);
+
+        let mut ser = Serializer::new(Vec::new()).unwrap();
+        ser.serialize_list_from_iter("", std::iter::empty::<i32>())
+            .unwrap();
+        let bytes = ser.finish().unwrap();
+        assert_eq!(bytes, expected);
+        assert_eq!(from_slice::<Vec<i32>>(&bytes).unwrap(), Vec::<i32>::new());
+
+        let mut ser = Serializer::new(Cursor::new(Vec::new())).unwrap();
+        ser.serialize_list_from_iter_seekable("", std::iter::empty::<i32>())
+            .unwrap();
+        assert_eq!(ser.finish().unwrap().into_inner(), expected);
+
+        assert_eq!(to_vec(&Vec::<i32>::new()).unwrap(), expected);
+    }
+
+    #[test]
+    fn int128_round_trip() {
+        let bytes = to_vec(&u128::MAX).unwrap();
+        assert_eq!(from_slice::<u128>(&bytes).unwrap(), u128::MAX);
+
+        for value in [
+            i128::MIN,
+            i128::MAX,
+            -170_141_183_460_469_231_731_687_303_715,
+            -1,
+            0,
+        ] {
+            let bytes = to_vec(&value).unwrap();
+            assert_eq!(from_slice::<i128>(&bytes).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn int128_truncated() {
+        let bytes = document(&[
+            0x07, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0, 0, 0, 0, 0, 0, 0, 0,
+        ]);
+        let err = from_slice::<u128>(&bytes).unwrap_err();
+        assert!(matches!(err, Error::Message(_)), "{err}");
+
+        let bytes = document(&[0x07, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0, 0, 0]);
+        let err = from_slice::<i128>(&bytes).unwrap_err();
+        assert!(matches!(err, Error::Eof), "{err}");
+    }
+
+    #[test]
+    fn serialize_to_buf() {
+        let shape = Shape::Rect(Size { w: 3, h: 4 });
+        let expected = to_vec(&shape).unwrap();
+
+        let mut buf = vec![0; expected.len()];
+        assert_eq!(to_buf(&mut buf, &shape).unwrap(), expected.len());
+        assert_eq!(buf, expected);
+
+        let mut buf = vec![0; expected.len() - 1];
+        let err = to_buf(&mut buf, &shape).unwrap_err();
+        assert!(matches!(err, Error::BufferFull), "{err}");
+    }
+
+    #[test]
+    fn list_of_compounds_into_values() {
+        let sizes = vec![
+            Size { w: 1, h: 2 },
+            Size { w: 3, h: 4 },
+            Size { w: 5, h: 6 },
+        ];
+        let bytes = to_vec(&sizes).unwrap();
+        let values = from_slice::<Vec<Value>>(&bytes).unwrap();
+
+        let expected: Vec<Value> = sizes
+            .iter()
+            .map(|size| {
+                Value::Compound(BTreeMap::from([
+                    ("w".to_string(), Value::Short(size.w as i16)),
+                    ("h".to_string(), Value::Short(size.h as i16)),
+                ]))
+            })
+            .collect();
+        assert_eq!(values, expected);
+        assert_eq!(to_vec(&values).unwrap(), to_vec(&expected).unwrap());
+    }
+
+    #[test]
+    fn signedness_is_erased() {
+        let bytes = to_vec(&-1i32).unwrap();
+        assert_eq!(bytes, to_vec(&u32::MAX).unwrap());
+        assert_eq!(from_slice::<i32>(&bytes).unwrap(), -1);
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), Value::Int(-1));
+        assert_eq!(from_slice::<u32>(&bytes).unwrap(), u32::MAX);
+    }
+
+    #[test]
+    fn borrowed_bytes() {
+        #[derive(Serialize, Deserialize)]
+        struct Blob<'a> {
+            #[serde(with = "serde_bytes")]
+            data: &'a [u8],
+        }
+
+        for data in [&[][..], &[1, 2, 3][..]] {
+            let bytes = to_vec(&Blob { data }).unwrap();
+            let blob = from_slice::<Blob>(&bytes).unwrap();
+            assert_eq!(blob.data, data);
+            assert!(bytes.as_ptr_range().contains(&blob.data.as_ptr()) || data.is_empty());
+        }
+    }
+
+    #[test]
+    fn owned_bytes() {
+        for data in [vec![], vec![1, 2, 3]] {
+            let bytes = to_vec(&ByteBuf::from(data.clone())).unwrap();
+            assert_eq!(from_reader::<_, ByteBuf>(&bytes[..]).unwrap(), data);
+            assert_eq!(from_slice::<ByteBuf>(&bytes).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn snbt_pretty() {
+        let value = Value::Compound(BTreeMap::from([
+            ("name".to_owned(), Value::String("say \"hi\"".to_owned())),
+            (
+                "motd".to_owned(),
+                Value::String("line one\r\n\tline two\u{7}".to_owned()),
+            ),
+            (
+                "pos".to_owned(),
+                Value::List(vec![Value::Double(0.5), Value::Double(-2.0)]),
+            ),
+            (
+                "nested key".to_owned(),
+                Value::Compound(BTreeMap::from([
+                    ("count".to_owned(), Value::Byte(3)),
+                    ("empty".to_owned(), Value::List(vec![])),
+                    ("id".to_owned(), Value::Long(7)),
+                ])),
+            ),
+            ("data".to_owned(), Value::ByteArray((0..20).collect())),
+        ]));
+        let expected = r#"{
+  data: [B; 0b, 1b, 2b, 3b, 4b, 5b, 6b, 7b, 8b, 9b, 10b, 11b, 12b, 13b, 14b, 15b, ... (20 bytes)],
+  motd: "line one\r\n\tline two\u0007",
+  name: "say \"hi\"",
+  "nested key": {
+    count: 3b,
+    empty: [],
+    id: 7L
+  },
+  pos: [
+    0.5d,
+    -2d
+  ]
+}"#;
+        assert_eq!(value.to_snbt_pretty(2), expected);
+        assert_eq!(
+            value.to_string(),
+            "{data:[B;0b,1b,2b,3b,4b,5b,6b,7b,8b,9b,10b,11b,12b,13b,14b,15b,16b,17b,18b,19b],\
+             motd:\"line one\\r\\n\\tline two\\u0007\",\
+             name:\"say \\\"hi\\\"\",\"nested key\":{count:3b,empty:[],id:7L},pos:[0.5d,-2d]}"
+        );
+    }
+
+    #[test]
+    fn long_array_round_trip() {
+        let array = LongArray((0..100_000).map(|i| i * 0x1_0000_0001 - 50_000).collect());
+        let bytes = to_vec(&array).unwrap();
+        assert_eq!(bytes.len(), HEADER.len() + 8 + 100_000 * 8);
+        for decoded in [
+            from_slice::<LongArray>(&bytes).unwrap(),
+            from_reader::<_, LongArray>(&bytes[..]).unwrap(),
+        ] {
+            assert_eq!(decoded.0.len(), 100_000);
+            assert_eq!(decoded.0[0], -50_000);
+            assert_eq!(decoded.0[1], 0x1_0000_0001 - 50_000);
+            assert_eq!(decoded.0[99_999], 99_999 * 0x1_0000_0001 - 50_000);
+            assert_eq!(decoded, array);
+        }
+    }
+
+    #[test]
+    fn int_array_big_endian() {
+        let mut doc = HEADER.to_vec();
+        doc[6] = 0x80;
+        doc.extend_from_slice(&[
+            0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0xff, 0xff, 0xff,
+            0xfe, 0x00,
+        ]);
+        assert_eq!(from_slice::<IntArray>(&doc).unwrap(), IntArray(vec![1, -2]));
+        assert_eq!(
+            from_reader::<_, IntArray>(&doc[..]).unwrap(),
+            IntArray(vec![1, -2])
+        );
+        assert_eq!(
+            from_slice::<Value>(&doc).unwrap(),
+            Value::List(vec![Value::Int(1), Value::Int(-2)])
+        );
+    }
+
+    #[test]
+    fn invalid_mutf8() {
+        // A lone high surrogate between two ASCII letters.
+        let doc = document(&[
+            0x08, 0x00, 0x00, 0x05, 0x00, b'a', 0xed, 0xa0, 0x80, b'b', 0x00,
+        ]);
+
+        let err = from_slice::<String>(&doc).unwrap_err();
+        assert!(matches!(err, Error::Mutf8(_)), "{err}");
+
+        let mut de = Deserializer::from_slice(&doc).unwrap();
+        de.set_lenient_mutf8(true);
+        let string = String::deserialize(&mut de).unwrap();
+        assert!(
+            string.starts_with('a') && string.ends_with('b'),
+            "{string:?}"
+        );
+        assert!(string.contains('\u{fffd}'), "{string:?}");
+    }
+
+    struct Version(u8, u8);
+
+    impl Serialize for Version {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(&format_args!("{}.{}", self.0, self.1))
+        }
+    }
+
+    #[test]
+    fn collect_str() {
+        assert_eq!(to_vec(&Version(1, 20)).unwrap(), to_vec("1.20").unwrap());
+
+        let addrs = [
+            IpAddr::from([127, 0, 0, 1]),
+            IpAddr::from([0x2001, 0xdb8, 0, 0, 0, 0, 0, 1]),
+        ];
+        for addr in addrs {
+            let bytes = to_vec(&addr).unwrap();
+            assert_eq!(from_slice::<IpAddr>(&bytes).unwrap(), addr);
+        }
+
+        // Strings that need re-encoding as MUTF-8 are formatted into the same scratch buffer.
+        struct Shown(&'static str);
+        impl Serialize for Shown {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self.0)
+            }
+        }
+        let words = vec![Shown("a\0b"), Shown("\u{1f600}"), Shown("plain")];
+        assert_eq!(
+            to_vec(&words).unwrap(),
+            to_vec(&["a\0b", "\u{1f600}", "plain"]).unwrap()
+        );
+
+        struct Broken;
+        impl std::fmt::Display for Broken {
+            fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result {
+                Err(std::fmt::Error)
+            }
+        }
+        impl Serialize for Broken {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+        assert!(matches!(to_vec(&Broken), Err(Error::Message(_))));
+    }
+
+    struct Evens(Vec<u32>);
+
+    impl Serialize for Evens {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.iter().filter(|n| *n % 2 == 0))
+        }
+    }
+
+    #[test]
+    fn collect_seq_unknown_len() {
+        let bytes = to_vec(&Evens((0..10).collect())).unwrap();
+        assert_eq!(bytes, to_vec(&[0u32, 2, 4, 6, 8]).unwrap());
+        assert_eq!(from_slice::<Vec<u32>>(&bytes).unwrap(), [0, 2, 4, 6, 8]);
+
+        let bytes = to_vec(&Evens(vec![1, 3])).unwrap();
+        assert_eq!(from_slice::<Vec<u32>>(&bytes).unwrap(), Vec::<u32>::new());
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn bytes_payload() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Chunk {
+            data: bytes::Bytes,
+        }
+
+        let chunk = Chunk {
+            data: bytes::Bytes::from((0..=255).cycle().take(100_000).collect::<Vec<u8>>()),
+        };
+        let bytes = to_vec(&chunk).unwrap();
+        assert_eq!(
+            bytes,
+            to_vec(
+                &Value::compound()
+                    .insert("data", Value::ByteArray(chunk.data.to_vec()))
+                    .build()
+            )
+            .unwrap()
+        );
+        assert_eq!(from_slice::<Chunk>(&bytes).unwrap(), chunk);
+        assert_eq!(from_reader::<_, Chunk>(&bytes[..]).unwrap(), chunk);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn decode_mmap() {
+        use std::io::Write;
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&to_vec(&Size { w: 3, h: 4 }).unwrap())
+            .unwrap();
+        // SAFETY: the temporary files are private to this test and nothing else writes to them.
+        let size: Size = unsafe { crate::from_mmap(file.path()) }.unwrap();
+        assert_eq!(size, Size { w: 3, h: 4 });
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let err = unsafe { crate::from_mmap::<_, Size>(file.path()) }.unwrap_err();
+        assert!(matches!(err, Error::Eof), "{err}");
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&HEADER[..3]).unwrap();
+        let err = unsafe { crate::from_mmap::<_, Size>(file.path()) }.unwrap_err();
+        assert!(matches!(err, Error::TruncatedHeader(3)), "{err}");
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Samples {
+        #[serde(with = "crate::codec::i8_array")]
+        data: Vec<i8>,
+    }
+
+    #[test]
+    fn i8_array_round_trip() {
+        let samples = Samples {
+            data: vec![0, 1, -1, i8::MIN, i8::MAX, -42],
+        };
+        let bytes = to_vec(&samples).unwrap();
+        #[derive(Serialize)]
+        struct Raw {
+            data: ByteBuf,
+        }
+        let raw = Raw {
+            data: ByteBuf::from(vec![0x00, 0x01, 0xff, 0x80, 0x7f, 0xd6]),
+        };
+        assert_eq!(bytes, to_vec(&raw).unwrap());
+        assert_eq!(from_slice::<Samples>(&bytes).unwrap(), samples);
+        assert_eq!(from_reader::<_, Samples>(&bytes[..]).unwrap(), samples);
+    }
+
+    #[test]
+    fn header_endianness_matches_numbers() {
+        let bytes = to_vec(&0x0102_0304u32).unwrap();
+        assert_eq!(bytes[6] & 0x80, 0, "big-endian flag set");
+        assert_eq!(bytes[HEADER.len() + 3..][..4], 0x0102_0304u32.to_le_bytes());
+        assert_eq!(from_slice::<u32>(&bytes).unwrap(), 0x0102_0304);
+
+        let mut swapped = bytes.clone();
+        swapped[6] |= 0x80;
+        assert_eq!(from_slice::<u32>(&swapped).unwrap(), 0x0403_0201);
+    }
+
+    #[derive(Serialize)]
+    struct Extended {
+        a: u8,
+        nested: Vec<Vec<Size>>,
+        empty: Vec<Size>,
+        b: u8,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Known {
+        a: u8,
+        b: u8,
+    }
+
+    #[test]
+    fn skip_nested_lists_of_compounds() {
+        let size = |w| Size { w, h: w + 1 };
+        let extended = Extended {
+            a: 1,
+            nested: vec![vec![size(1), size(2)], vec![], vec![size(3)]],
+            empty: vec![],
+            b: 2,
+        };
+        let bytes = to_vec(&extended).unwrap();
+        assert_eq!(from_slice::<Known>(&bytes).unwrap(), Known { a: 1, b: 2 });
+        assert_eq!(
+            from_reader::<_, Known>(&bytes[..]).unwrap(),
+            Known { a: 1, b: 2 }
+        );
+        assert_eq!(
+            root_keys(&bytes[..]).unwrap(),
+            ["a", "nested", "empty", "b"]
+        );
+    }
+
+    #[test]
+    fn to_writer_flushes() {
+        let size = Size { w: 5, h: 6 };
+        let mut writer = BufWriter::with_capacity(1024, Vec::new());
+        to_writer(&mut writer, &size).unwrap();
+        assert_eq!(*writer.get_ref(), to_vec(&size).unwrap());
+    }
+
+    #[test]
+    fn zero_u8_field_is_not_a_terminator() {
+        let doc = document(&[
+            0x0a, 0x00, 0x00, // root compound
+            0x01, 0x01, 0x00, b'a', 0x00, // a: 0
+            0x01, 0x01, 0x00, b'b', 0x00, // b: 0
+            0x00, 0x00,
+        ]);
+        assert_eq!(from_slice::<Known>(&doc).unwrap(), Known { a: 0, b: 0 });
+        assert_eq!(to_vec(&Known { a: 0, b: 0 }).unwrap(), doc);
+    }
+
+    #[test]
+    fn build_values() {
+        let value = Value::compound()
+            .insert("name", "gravel")
+            .insert("count", 64i8)
+            .insert("pos", Value::list(vec![1.5f64, -2.0, 3.25]).unwrap())
+            .insert("tags", Value::compound().insert("id", 13i32).build())
+            .build();
+        let bytes = to_vec(&value).unwrap();
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), value);
+        assert_eq!(
+            value.to_string(),
+            "{count:64b,name:\"gravel\",pos:[1.5d,-2d,3.25d],tags:{id:13}}"
+        );
+
+        let err = Value::list(vec![Value::Int(1), Value::from("two")]).unwrap_err();
+        assert!(
+            matches!(err, Error::TagMismatch(Tag::Int, Tag::String)),
+            "{err}"
+        );
+        assert_eq!(
+            Value::list(Vec::<Value>::new()).unwrap(),
+            Value::List(vec![])
+        );
+    }
+
+    #[test]
+    fn string_len_counts_encoded_bytes() {
+        for string in ["h\u{e9}llo", "nul\0byte", "globe \u{1f30d}"] {
+            let bytes = to_vec(string).unwrap();
+            let len = u16::from_le_bytes([bytes[10], bytes[11]]) as usize;
+            // Everything between the length and the trailing terminator is the string.
+            assert_eq!(len, bytes.len() - 13, "{string:?}");
+            assert_eq!(from_slice::<String>(&bytes).unwrap(), string);
+        }
+    }
+
+    #[test]
+    fn strings_share_encoding_buffer() {
+        // Each string is encoded in the same scratch buffer, so a long one followed by a short
+        // one must not leave any of its bytes behind.
+        let strings = [
+            "\u{1f30d}\0".repeat(100),
+            "a\0".to_string(),
+            "plain".to_string(),
+            "\0".to_string(),
+            String::new(),
+        ];
+        let bytes = to_vec(&strings).unwrap();
+        assert_eq!(from_slice::<Vec<String>>(&bytes).unwrap(), strings);
+
+        let mut body = vec![0x09, 0x00, 0x00, 0x08, 0x05, 0x00, 0x00, 0x00];
+        for string in &strings {
+            let mutf8 = mutf8::utf8_to_mutf8(string.as_bytes()).unwrap();
+            body.extend_from_slice(&(mutf8.len() as u16).to_le_bytes());
+            body.extend_from_slice(&mutf8);
+        }
+        body.push(0x00);
+        assert_eq!(bytes, document(&body));
+    }
+
+    #[test]
+    fn string_len_mid_sequence() {
+        // Declares one byte of the two byte encoding of an e with an acute accent.
+        let doc = document(&[0x08, 0x00, 0x00, 0x01, 0x00, 0xc3, 0xa9, 0x00]);
+        let err = from_slice::<String>(&doc).unwrap_err();
+        assert!(matches!(err, Error::Mutf8(_)), "{err}");
+        let err = from_reader::<_, String>(&doc[..]).unwrap_err();
+        assert!(matches!(err, Error::Mutf8(_)), "{err}");
+    }
+
+    #[test]
+    fn coerce_integer_widths() {
+        #[derive(Serialize)]
+        struct Old {
+            count: u16,
+            offset: i16,
+        }
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct New {
+            count: u32,
+            offset: i64,
+        }
+        #[derive(Deserialize, Debug)]
+        struct Narrow {
+            #[allow(dead_code)]
+            count: u16,
+        }
+
+        let old = to_vec(&Old {
+            count: 40_000,
+            offset: -5,
+        })
+        .unwrap();
+        let err = from_slice::<New>(&old).unwrap_err();
+        assert!(
+            matches!(err, Error::TagMismatch(Tag::Int, Tag::Short)),
+            "{err}"
+        );
+
+        let mut de = Deserializer::from_slice(&old).unwrap();
+        de.set_coerce_integers(true);
+        assert_eq!(
+            New::deserialize(&mut de).unwrap(),
+            New {
+                count: 40_000,
+                offset: -5
+            }
+        );
+
+        let wide = to_vec(&New {
+            count: 70_000,
+            offset: 0,
+        })
+        .unwrap();
+        let mut de = Deserializer::from_slice(&wide).unwrap();
+        de.set_coerce_integers(true);
+        let err = Narrow::deserialize(&mut de).unwrap_err();
+        assert!(err.to_string().contains("70000"), "{err}");
+    }
+
+    #[test]
+    fn coerce_float_widths() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Single {
+            speed: f32,
+        }
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Double {
+            speed: f64,
+        }
+
+        let single = to_vec(&Single { speed: 0.1 }).unwrap();
+        let err = from_slice::<Double>(&single).unwrap_err();
+        assert!(
+            matches!(err, Error::TagMismatch(Tag::Double, Tag::Float)),
+            "{err}"
+        );
+        let mut de = Deserializer::from_slice(&single).unwrap();
+        de.set_coerce_floats(true);
+        assert_eq!(
+            Double::deserialize(&mut de).unwrap(),
+            Double {
+                speed: 0.1f32.into()
+            }
+        );
+
+        let double = to_vec(&Double { speed: 0.1 }).unwrap();
+        let err = from_slice::<Single>(&double).unwrap_err();
+        assert!(
+            matches!(err, Error::TagMismatch(Tag::Float, Tag::Double)),
+            "{err}"
+        );
+        let mut de = Deserializer::from_slice(&double).unwrap();
+        de.set_coerce_floats(true);
+        let narrowed = Single::deserialize(&mut de).unwrap();
+        assert_eq!(narrowed, Single { speed: 0.1 });
+        assert_ne!(f64::from(narrowed.speed), 0.1);
+
+        let huge = to_vec(&Double { speed: -1e300 }).unwrap();
+        let mut de = Deserializer::from_slice(&huge).unwrap();
+        de.set_coerce_floats(true);
+        assert_eq!(
+            Single::deserialize(&mut de).unwrap(),
+            Single {
+                speed: f32::NEG_INFINITY
+            }
+        );
+    }
+
+    #[test]
+    fn sets_as_lists() {
+        let ints = HashSet::from([3, -1, 7, 1 << 20]);
+        let bytes = to_vec(&ints).unwrap();
+        assert_eq!(bytes[HEADER.len()], Tag::List as u8);
+        assert_eq!(from_slice::<HashSet<i32>>(&bytes).unwrap(), ints);
+
+        let strings = BTreeSet::from(["b".to_owned(), "a".to_owned(), "c".to_owned()]);
+        let bytes = to_vec(&strings).unwrap();
+        assert_eq!(bytes, to_vec(&["a", "b", "c"]).unwrap());
+        assert_eq!(from_slice::<BTreeSet<String>>(&bytes).unwrap(), strings);
+
+        for bytes in [
+            to_vec(&HashSet::<i32>::new()).unwrap(),
+            to_vec(&BTreeSet::<String>::new()).unwrap(),
+        ] {
+            assert_eq!(bytes, document(&[0x09, 0x00, 0x00, 0x00, 0, 0, 0, 0, 0x00]));
+            assert!(from_slice::<HashSet<i32>>(&bytes).unwrap().is_empty());
+            assert!(from_slice::<BTreeSet<String>>(&bytes).unwrap().is_empty());
+        }
+
+        let duplicates = to_vec(&[1, 2, 1]).unwrap();
+        assert_eq!(
+            from_slice::<BTreeSet<i32>>(&duplicates).unwrap(),
+            BTreeSet::from([1, 2])
+        );
+    }
+
+    #[test]
+    fn splice_raw_compound() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Outer {
+            before: u8,
+            size: Size,
+            after: u8,
+        }
+
+        let size = to_vec(&Size { w: 8, h: 9 }).unwrap();
+        // Strip the header, the root tag and name, and the document terminator.
+        let body = &size[HEADER.len() + 3..size.len() - 1];
+
+        let mut serializer = Serializer::new(Vec::new()).unwrap();
+        let mut map = serde::Serializer::serialize_map(&mut serializer, None).unwrap();
+        SerializeMap::serialize_entry(&mut map, "before", &1u8).unwrap();
+        map.serialize_raw("size", Tag::Compound, body).unwrap();
+        SerializeMap::serialize_entry(&mut map, "after", &2u8).unwrap();
+        SerializeMap::end(map).unwrap();
+        let bytes = serializer.finish().unwrap();
+
+        assert_eq!(
+            from_slice::<Outer>(&bytes).unwrap(),
+            Outer {
+                before: 1,
+                size: Size { w: 8, h: 9 },
+                after: 2
+            }
+        );
+    }
+
+    #[test]
+    fn options() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Maybe {
+            a: Option<u8>,
+            b: Option<u8>,
+        }
+
+        let maybe = Maybe {
+            a: None,
+            b: Some(2),
+        };
+        let bytes = to_vec(&maybe).unwrap();
+        assert_eq!(root_keys(&bytes[..]).unwrap(), ["b"]);
+        assert_eq!(from_slice::<Maybe>(&bytes).unwrap(), maybe);
+
+        let bytes = to_vec(&None::<u8>).unwrap();
+        assert_eq!(bytes, document(&[0x00]));
+        assert_eq!(from_slice::<Option<u8>>(&bytes).unwrap(), None);
+        assert_eq!(
+            from_slice::<Option<u8>>(&to_vec(&7u8).unwrap()).unwrap(),
+            Some(7)
+        );
+
+        let err = to_vec(&vec![Some(1), None, Some(3)]).unwrap_err();
+        assert!(matches!(err, Error::NoneInList), "{err}");
+        let err = to_vec(&vec![None::<i32>, None]).unwrap_err();
+        assert!(matches!(err, Error::NoneInList), "{err}");
+
+        let bytes = to_vec(&vec![Some(1), Some(3)]).unwrap();
+        assert_eq!(bytes, to_vec(&vec![1, 3]).unwrap());
+        assert_eq!(
+            from_slice::<Vec<Option<i32>>>(&bytes).unwrap(),
+            [Some(1), Some(3)]
+        );
+    }
+
+    #[serde_with::serde_as]
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Timeouts {
+        #[serde_as(as = "IfIsHumanReadable<DurationSeconds<String>, DurationSeconds<u64>>")]
+        connect: Duration,
+        #[serde_as(as = "Vec<IfIsHumanReadable<DurationSeconds<String>, DurationSeconds<u64>>>")]
+        retries: Vec<Duration>,
+    }
+
+    #[test]
+    fn human_readable_modes() {
+        let timeouts = Timeouts {
+            connect: Duration::from_secs(30),
+            retries: vec![Duration::from_secs(1), Duration::from_secs(5)],
+        };
+        for (human_readable, tag) in [(false, Value::Long(30)), (true, Value::from("30"))] {
+            let mut serializer = Serializer::new(Vec::new()).unwrap();
+            serializer.set_human_readable(human_readable);
+            timeouts.serialize(&mut serializer).unwrap();
+            let bytes = serializer.finish().unwrap();
+
+            let Value::Compound(fields) = from_slice::<Value>(&bytes).unwrap() else {
+                panic!("expected a compound");
+            };
+            assert_eq!(fields["connect"], tag);
+            let Value::List(retries) = &fields["retries"] else {
+                panic!("expected a list");
+            };
+            assert_eq!(retries[0].tag(), tag.tag());
+
+            let mut de = Deserializer::from_slice(&bytes).unwrap();
+            de.set_human_readable(human_readable);
+            assert_eq!(Timeouts::deserialize(&mut de).unwrap(), timeouts);
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Partial {
+        a: u8,
+        #[serde(flatten)]
+        extra: HashMap<String, Value>,
+    }
+
+    #[test]
+    fn flatten_catch_all() {
+        let full = Value::compound()
+            .insert("a", 1i8)
+            .insert("list", Value::list(vec![1i32, 2]).unwrap())
+            .insert("name", "x")
+            .insert("nested", Value::compound().insert("b", 2i16).build())
+            .build();
+        let bytes = to_vec(&full).unwrap();
+
+        let partial = from_slice::<Partial>(&bytes).unwrap();
+        assert_eq!(partial.a, 1);
+        assert_eq!(partial.extra.len(), 3);
+        assert_eq!(partial.extra["name"], Value::from("x"));
+        assert_eq!(
+            partial.extra["nested"],
+            Value::compound().insert("b", 2i16).build()
+        );
+
+        // The catch-all is written back after the named fields, in its own order.
+        let bytes = to_vec(&partial).unwrap();
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), full);
+        assert_eq!(from_slice::<Partial>(&bytes).unwrap(), partial);
+
+        // A repeated unknown key keeps its last value.
+        let doc = document(&[
+            0x0a, 0x00, 0x00, // root compound
+            0x01, 0x01, 0x00, b'a', 0x05, // a: 5
+            0x01, 0x01, 0x00, b'x', 0x01, // x: 1
+            0x01, 0x01, 0x00, b'x', 0x02, // x: 2
+            0x00, 0x00,
+        ]);
+        let partial = from_slice::<Partial>(&doc).unwrap();
+        assert_eq!(partial.a, 5);
+        assert_eq!(
+            partial.extra,
+            HashMap::from([("x".to_owned(), Value::Byte(2))])
+        );
+    }
+
+    #[test]
+    fn patch_fields() {
+        let original = Value::compound()
+            .insert("time", 100i64)
+            .insert("name", "old")
+            .insert(
+                "items",
+                Value::list(vec![
+                    Value::compound().insert("id", "a").build(),
+                    Value::compound().insert("id", "b").build(),
+                ])
+                .unwrap(),
+            )
+            .insert("z", 1i8)
+            .build();
+        let mut bytes = to_vec(&original).unwrap();
+        let len = bytes.len();
+
+        patch_field(&mut bytes, &["time"], Value::Long(200)).unwrap();
+        assert_eq!(bytes.len(), len);
+        patch_field(&mut bytes, &["name"], "much longer".into()).unwrap();
+        patch_field(&mut bytes, &["items", "0", "id"], "abc".into()).unwrap();
+        patch_field(&mut bytes, &["items", "1", "id"], Value::Int(7)).unwrap();
+
+        let expected = Value::compound()
+            .insert("time", 200i64)
+            .insert("name", "much longer")
+            .insert(
+                "items",
+                Value::list(vec![
+                    Value::compound().insert("id", "abc").build(),
+                    Value::compound().insert("id", 7i32).build(),
+                ])
+                .unwrap(),
+            )
+            .insert("z", 1i8)
+            .build();
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), expected);
+
+        for path in [
+            &["missing"][..],
+            &["items", "2"],
+            &["items", "x"],
+            &["z", "y"],
+        ] {
+            let err = patch_field(&mut bytes, path, Value::Byte(0)).unwrap_err();
+            assert!(matches!(err, Error::FieldNotFound(_)), "{err}");
+        }
+        let err = patch_field(&mut bytes, &["items", "0"], Value::Byte(0)).unwrap_err();
+        assert!(
+            matches!(err, Error::TagMismatch(Tag::Compound, Tag::Byte)),
+            "{err}"
+        );
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), expected);
+
+        // A value that fails to encode leaves the document untouched, tag included.
+        let before = bytes.clone();
+        let mixed = Value::List(vec![Value::Int(1), Value::String("a".to_owned())]);
+        let err = patch_field(&mut bytes, &["name"], mixed).unwrap_err();
+        assert!(
+            matches!(err, Error::TagMismatch(Tag::Int, Tag::String)),
+            "{err}"
+        );
+        assert_eq!(bytes, before);
+    }
+
+    #[test]
+    fn tuples_as_compounds() {
+        let tuple = (7u8, "seven".to_owned(), 7.5f64);
+        let err = to_vec(&tuple).unwrap_err();
+        assert!(
+            matches!(err, Error::TagMismatch(Tag::Byte, Tag::String)),
+            "{err}"
+        );
+
+        let mut serializer = Serializer::new(Vec::new()).unwrap();
+        serializer.set_tuple_repr(TupleRepr::Compound);
+        tuple.serialize(&mut serializer).unwrap();
+        let bytes = serializer.finish().unwrap();
+        assert_eq!(
+            from_slice::<Value>(&bytes).unwrap(),
+            Value::compound()
+                .insert("0", 7i8)
+                .insert("1", "seven")
+                .insert("2", 7.5f64)
+                .build()
+        );
+
+        let mut de = Deserializer::from_slice(&bytes).unwrap();
+        de.set_tuple_repr(TupleRepr::Compound);
+        assert_eq!(<(u8, String, f64)>::deserialize(&mut de).unwrap(), tuple);
+
+        let mut de = Deserializer::from_slice(&bytes).unwrap();
+        de.set_tuple_repr(TupleRepr::Compound);
+        assert!(<(u8, String)>::deserialize(&mut de).is_err());
+    }
+
+    #[test]
+    fn dry_run_stats() {
+        let value = Value::compound()
+            .insert("name", "abc")
+            .insert(
+                "sizes",
+                Value::list(vec![
+                    Value::compound()
+                        .insert("w", 1i16)
+                        .insert("h", 2i16)
+                        .build(),
+                    Value::compound().insert("w", 3i16).build(),
+                ])
+                .unwrap(),
+            )
+            .build();
+        let stats = dry_run(&value).unwrap();
+        assert_eq!(stats.total_bytes, to_vec(&value).unwrap().len());
+
+        let mut expected = [0; 16];
+        expected[Tag::Short as usize] = 3;
+        expected[Tag::String as usize] = 1;
+        expected[Tag::List as usize] = 1;
+        expected[Tag::Compound as usize] = 3;
+        assert_eq!(stats.tag_counts, expected);
+
+        let evens = dry_run(&Evens((0..10).collect())).unwrap();
+        assert_eq!(evens.tag_counts[Tag::Int as usize], 5);
+        assert_eq!(evens.tag_counts[Tag::List as usize], 1);
+
+        // The payload of an internally tagged newtype variant shares the variant's compound.
+        let repr = EnumRepr::Internal("type");
+        let rect = Shape::Rect(Size { w: 3, h: 4 });
+        let stats = crate::ser::measure(&rect, repr).unwrap();
+        assert_eq!(stats.total_bytes, to_vec_repr(&rect, repr).unwrap().len());
+        let mut expected = [0; 16];
+        expected[Tag::Short as usize] = 2;
+        expected[Tag::String as usize] = 1;
+        expected[Tag::Compound as usize] = 1;
+        assert_eq!(stats.tag_counts, expected);
+    }
+
+    #[test]
+    fn list_len_bounds() {
+        // Element tag Int with a count of -1.
+        let doc = document(&[0x09, 0x00, 0x00, 0x03, 0xff, 0xff, 0xff, 0xff, 0x00]);
+        let err = from_slice::<Vec<i32>>(&doc).unwrap_err();
+        assert!(matches!(err, Error::NegativeLen(-1)), "{err}");
+        let err = from_slice::<Value>(&doc).unwrap_err();
+        assert!(matches!(err, Error::NegativeLen(-1)), "{err}");
+        let err = root_keys(
+            &document(&[
+                0x0a, 0x00, 0x00, 0x09, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x80,
+            ])[..],
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::NegativeLen(i32::MIN)), "{err}");
+
+        // The largest possible count is accepted, but the elements run out.
+        let doc = document(&[0x09, 0x00, 0x00, 0x04, 0xff, 0xff, 0xff, 0x7f, 0x00]);
+        for result in [
+            from_slice::<Vec<i64>>(&doc).map(drop),
+            from_reader::<_, Vec<i64>>(&doc[..]).map(drop),
+            from_slice::<Value>(&doc).map(drop),
+        ] {
+            let err = result.unwrap_err();
+            assert!(matches!(err, Error::Eof), "{err}");
+        }
+    }
+
+    struct Unsized<'a>(&'a BTreeMap<String, u32>);
+
+    impl Serialize for Unsized<'_> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut map = serializer.serialize_map(None)?;
+            for (key, value) in self.0 {
+                map.serialize_entry(key, value)?;
+            }
+            map.end()
+        }
+    }
+
+    #[test]
+    fn large_maps() {
+        let map: BTreeMap<String, u32> = (0..10_000).map(|i| (format!("key{i}"), i)).collect();
+        let bytes = to_vec(&map).unwrap();
+        assert_eq!(from_slice::<BTreeMap<String, u32>>(&bytes).unwrap(), map);
+        assert_eq!(to_vec(&Unsized(&map)).unwrap(), bytes);
+    }
+
+    #[test]
+    fn headless_decoding() {
+        let bytes = to_vec(&Known { a: 1, b: 2 }).unwrap();
+        let mut input = &bytes[..];
+        let header = read_header(&mut input).unwrap();
+        assert!(!header.is_big_endian());
+        let mut de = Deserializer::headless(SliceRead::new(input), header);
+        assert_eq!(Known::deserialize(&mut de).unwrap(), Known { a: 1, b: 2 });
+
+        let mut reader = Cursor::new(document(&[0x03, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04]));
+        reader.get_mut()[6] = 0x80;
+        let header = read_header(&mut reader).unwrap();
+        assert!(header.is_big_endian());
+        let mut de = Deserializer::headless(IoRead::new(reader), header);
+        assert_eq!(i32::deserialize(&mut de).unwrap(), 0x0102_0304);
+
+        assert!(matches!(
+            read_header(&bytes[3..]),
+            Err(Error::InvalidHeader)
+        ));
+        assert!(matches!(
+            read_header(&bytes[..4]),
+            Err(Error::TruncatedHeader(4))
+        ));
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Endpoint {
+        #[serde(with = "crate::codec::net::socket_addr")]
+        listen: SocketAddr,
+        #[serde(with = "crate::codec::net::ipv4")]
+        gateway: Ipv4Addr,
+        #[serde(with = "crate::codec::net::ipv6")]
+        resolver: Ipv6Addr,
+    }
+
+    #[test]
+    fn net_addresses() {
+        let mapped = Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped();
+        let endpoints = [
+            Endpoint {
+                listen: "192.0.2.1:25565".parse().unwrap(),
+                gateway: Ipv4Addr::new(255, 255, 255, 254),
+                resolver: mapped,
+            },
+            Endpoint {
+                listen: "[2001:db8::1]:443".parse().unwrap(),
+                gateway: Ipv4Addr::UNSPECIFIED,
+                resolver: Ipv6Addr::LOCALHOST,
+            },
+            Endpoint {
+                listen: SocketAddr::new(mapped.into(), 80),
+                gateway: Ipv4Addr::LOCALHOST,
+                resolver: Ipv6Addr::UNSPECIFIED,
+            },
+        ];
+        for endpoint in &endpoints {
+            let bytes = to_vec(endpoint).unwrap();
+            assert_eq!(&from_slice::<Endpoint>(&bytes).unwrap(), endpoint);
+        }
+
+        let bytes = to_vec(&endpoints[0]).unwrap();
+        let Value::Compound(fields) = from_slice::<Value>(&bytes).unwrap() else {
+            panic!("expected a compound");
+        };
+        assert_eq!(
+            fields["listen"],
+            Value::compound()
+                .insert("ip", 0xc000_0201_u32 as i32)
+                .insert("port", 25565_u16 as i16)
+                .build()
+        );
+        assert_eq!(
+            fields["resolver"],
+            Value::ByteArray(mapped.octets().to_vec())
+        );
+    }
+
+    #[test]
+    fn merge_values() {
+        let mut config = Value::compound()
+            .insert("name", "server")
+            .insert(
+                "net",
+                Value::compound()
+                    .insert("port", 25565)
+                    .insert("host", "0.0.0.0")
+                    .build(),
+            )
+            .insert("motd", Value::compound().insert("text", "hi").build())
+            .insert("ops", Value::list(vec!["alice"]).unwrap())
+            .build();
+        let overrides = Value::compound()
+            .insert("net", Value::compound().insert("port", 25566).build())
+            .insert("motd", "plain")
+            .insert("ops", Value::list(vec!["bob"]).unwrap())
+            .insert("seed", 42i64)
+            .build();
+        config.merge(overrides);
+        assert_eq!(
+            config,
+            Value::compound()
+                .insert("name", "server")
+                .insert(
+                    "net",
+                    Value::compound()
+                        .insert("port", 25566)
+                        .insert("host", "0.0.0.0")
+                        .build(),
+                )
+                .insert("motd", "plain")
+                .insert("ops", Value::list(vec!["bob"]).unwrap())
+                .insert("seed", 42i64)
+                .build()
+        );
+
+        let mut scalar = Value::Int(1);
+        let compound = Value::compound().insert("a", 1).build();
+        scalar.merge(compound.clone());
+        assert_eq!(scalar, compound);
+    }
+
+    #[test]
+    fn header_failures() {
+        let bytes = to_vec(&Known { a: 1, b: 2 }).unwrap();
+        for input in [&[][..], &bytes[..3]] {
+            let slice = from_slice::<Known>(input).unwrap_err();
+            let reader = from_reader::<_, Known>(input).unwrap_err();
+            match input.len() {
+                0 => assert!(matches!((slice, reader), (Error::Eof, Error::Eof))),
+                len => assert!(matches!(
+                    (slice, reader),
+                    (Error::TruncatedHeader(a), Error::TruncatedHeader(b)) if a == len && b == len
+                )),
+            }
+        }
+
+        let mut wrong = bytes.clone();
+        wrong[1] = b'X';
+        assert!(matches!(
+            from_slice::<Known>(&wrong),
+            Err(Error::InvalidHeader)
+        ));
+        // A short input that already disagrees with the magic isn't a truncated header.
+        assert!(matches!(
+            from_slice::<Known>(b"{\""),
+            Err(Error::InvalidHeader)
+        ));
+
+        struct Failing;
+        impl std::io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::PermissionDenied.into())
+            }
+        }
+        assert!(matches!(
+            from_reader::<_, Known>(Failing),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn borrowed_values() {
+        fn decode(bytes: &[u8]) -> ValueRef<'_> {
+            from_slice(bytes).unwrap()
+        }
+        fn borrows(input: &[u8], string: &str) -> bool {
+            input.as_ptr_range().contains(&string.as_ptr())
+        }
+
+        let value = Value::compound()
+            .insert("plain", "borrowed")
+            .insert("nul", "not\0borrowed")
+            .insert("globe", "\u{1f30d}")
+            .insert("data", Value::ByteArray(vec![1, 2, 3]))
+            .build();
+        let bytes = to_vec(&value).unwrap();
+        let ValueRef::Compound(fields) = decode(&bytes) else {
+            panic!("expected a compound");
+        };
+        for (key, field) in &fields {
+            assert!(
+                matches!(key, Cow::Borrowed(k) if borrows(&bytes, k)),
+                "{key}"
+            );
+            match (key.as_ref(), field) {
+                ("plain", ValueRef::String(Cow::Borrowed(s))) => assert!(borrows(&bytes, s)),
+                ("nul" | "globe", ValueRef::String(Cow::Owned(_))) => {}
+                ("data", ValueRef::ByteArray(Cow::Borrowed(_))) => {}
+                _ => panic!("unexpected field {key}: {field:?}"),
+            }
+        }
+        assert_eq!(ValueRef::Compound(fields).into_owned(), value);
+
+        let mut de = Deserializer::from_reader(&bytes[..]).unwrap();
+        let owned = ValueRef::deserialize(&mut de).unwrap();
+        assert!(
+            matches!(&owned, ValueRef::Compound(fields) if fields.keys().all(|k| matches!(k, Cow::Owned(_))))
+        );
+        assert_eq!(to_vec(&owned).unwrap(), bytes);
+    }
+
+    #[test]
+    fn unit_fields() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Marker;
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Tagged {
+            a: u8,
+            nothing: (),
+            marker: Marker,
+            b: u8,
+        }
+
+        let tagged = Tagged {
+            a: 1,
+            nothing: (),
+            marker: Marker,
+            b: 2,
+        };
+        let bytes = to_vec(&tagged).unwrap();
+        assert_eq!(
+            bytes,
+            document(&[
+                0x0a, 0x00, 0x00, // root
+                0x01, 0x01, 0x00, b'a', 0x01, // a
+                0x0a, 0x07, 0x00, b'n', b'o', b't', b'h', b'i', b'n', b'g', 0x00, // nothing
+                0x0a, 0x06, 0x00, b'm', b'a', b'r', b'k', b'e', b'r', 0x00, // marker
+                0x01, 0x01, 0x00, b'b', 0x02, // b
+                0x00, // end of root
+                0x00, // end of document
+            ])
+        );
+        assert_eq!(from_slice::<Tagged>(&bytes).unwrap(), tagged);
+        assert_eq!(
+            from_slice::<Vec<()>>(&to_vec(&[(), ()]).unwrap()).unwrap(),
+            [(), ()]
+        );
+    }
+
+    #[test]
+    fn chars() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Glyph {
+            c: char,
+        }
+
+        for c in ['x', '\u{e9}', '\0', '\u{1f30d}'] {
+            let bytes = to_vec(&Glyph { c }).unwrap();
+            assert_eq!(
+                bytes,
+                to_vec(&BTreeMap::from([("c", c.to_string())])).unwrap()
+            );
+            assert_eq!(from_slice::<Glyph>(&bytes).unwrap(), Glyph { c });
+        }
+
+        for string in ["", "xy"] {
+            let bytes = to_vec(&BTreeMap::from([("c", string)])).unwrap();
+            let err = from_slice::<Glyph>(&bytes).unwrap_err();
+            assert!(err.to_string().contains("a single character"), "{err}");
+        }
+    }
+
+    #[test]
+    fn max_output() {
+        let map: BTreeMap<String, Vec<i32>> = (0..100)
+            .map(|i| (format!("key{i}"), (0..100).collect()))
+            .collect();
+        let expected = to_vec(&map).unwrap();
+
+        let mut out = Vec::new();
+        let mut ser = Serializer::new(&mut out).unwrap();
+        ser.set_max_output(1000);
+        let err = map.serialize(&mut ser).unwrap_err();
+        assert!(matches!(err, Error::LimitExceeded(_, 1000)), "{err}");
+        assert!(out.len() <= 1000);
+
+        // A list of unknown length is held in memory, and counts towards the limit as it grows.
+        let mut ser = Serializer::new(Vec::new()).unwrap();
+        ser.set_max_output(1000);
+        let err = ser
+            .serialize_list_from_iter("values", 0..1_000_000i32)
+            .unwrap_err();
+        assert!(matches!(err, Error::LimitExceeded(_, 1000)), "{err}");
+
+        let mut ser = Serializer::new(Vec::new()).unwrap();
+        ser.set_max_output(expected.len());
+        map.serialize(&mut ser).unwrap();
+        assert_eq!(ser.finish().unwrap(), expected);
+    }
+
+    #[test]
+    fn tuple_structs() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Rgb(u8, u8, u8);
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Meters(u16);
+
+        let bytes = to_vec(&Rgb(255, 128, 0)).unwrap();
+        assert_eq!(bytes, to_vec(&[255u8, 128, 0]).unwrap());
+        assert_eq!(from_slice::<Rgb>(&bytes).unwrap(), Rgb(255, 128, 0));
+
+        let mut ser = Serializer::new(Vec::new()).unwrap();
+        ser.set_tuple_repr(TupleRepr::Compound);
+        Rgb(1, 2, 3).serialize(&mut ser).unwrap();
+        let bytes = ser.finish().unwrap();
+        let mut de = Deserializer::from_slice(&bytes).unwrap();
+        de.set_tuple_repr(TupleRepr::Compound);
+        assert_eq!(Rgb::deserialize(&mut de).unwrap(), Rgb(1, 2, 3));
+
+        // A single field tuple struct is a newtype and is written as its field.
+        assert_eq!(to_vec(&Meters(5)).unwrap(), to_vec(&5u16).unwrap());
+        assert_eq!(
+            from_slice::<Meters>(&to_vec(&5u16).unwrap()).unwrap(),
+            Meters(5)
+        );
+
+        for len in [2, 4] {
+            let bytes = to_vec(&vec![1u8; len]).unwrap();
+            let err = from_slice::<Rgb>(&bytes).unwrap_err();
+            assert!(err.to_string().contains("a list of 3 elements"), "{err}");
+        }
+    }
+
+    #[test]
+    fn swap_byte_order() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Chunk {
+            name: String,
+            data: ByteBuf,
+            heights: IntArray,
+            ticks: LongArray,
+            grid: Vec<Vec<i16>>,
+            sections: Vec<Size>,
+            scale: f64,
+        }
+
+        let chunk = Chunk {
+            name: "spawn \u{1f30d}".to_string(),
+            data: ByteBuf::from(vec![1, 2, 3, 4]),
+            heights: IntArray(vec![64, -1, 0x0102_0304]),
+            ticks: LongArray(vec![i64::MIN, 7]),
+            grid: vec![vec![1, 2], vec![], vec![-3]],
+            sections: vec![Size { w: 1, h: 0x0203 }, Size { w: 4, h: 5 }],
+            scale: 0.5,
+        };
+        let original = to_vec(&chunk).unwrap();
+        let mut bytes = original.clone();
+        swap_endianness(&mut bytes).unwrap();
+        assert_eq!(bytes[6], 0x80);
+        assert_ne!(bytes, original);
+        assert_eq!(from_slice::<Chunk>(&bytes).unwrap(), chunk);
+        swap_endianness(&mut bytes).unwrap();
+        assert_eq!(bytes, original);
+
+        let mut int = to_vec(&0x0102_0304).unwrap();
+        swap_endianness(&mut int).unwrap();
+        let mut expected = document(&[0x03, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04, 0x00]);
+        expected[6] = 0x80;
+        assert_eq!(int, expected);
+
+        let mut truncated = original[..original.len() - 8].to_vec();
+        assert!(matches!(swap_endianness(&mut truncated), Err(Error::Eof)));
+
+        // A 128-bit integer is kept as a byte array, which has no byte order to swap.
+        let mut wide = to_vec(&0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10u128).unwrap();
+        swap_endianness(&mut wide).unwrap();
+        assert_eq!(
+            from_slice::<u128>(&wide).unwrap(),
+            0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10
+        );
+        let mut wide = to_vec(&-0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10i128).unwrap();
+        swap_endianness(&mut wide).unwrap();
+        assert_eq!(
+            from_slice::<i128>(&wide).unwrap(),
+            -0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10
+        );
+
+        // Nesting is bounded like it is when decoding, instead of overflowing the stack.
+        let mut deep = document(&[0x09, 0x00, 0x00]);
+        for _ in 0..200_000 {
+            deep.extend_from_slice(&[0x09, 0x01, 0x00, 0x00, 0x00]);
+        }
+        assert!(matches!(
+            swap_endianness(&mut deep),
+            Err(Error::TooDeep(512))
+        ));
+        let mut shallow = document(&[0x09, 0x00, 0x00]);
+        for _ in 0..100 {
+            shallow.extend_from_slice(&[0x09, 0x01, 0x00, 0x00, 0x00]);
+        }
+        shallow.extend_from_slice(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        swap_endianness(&mut shallow).unwrap();
+    }
+
+    #[test]
+    fn reported_header() {
+        let mut bytes = to_vec(&1i32).unwrap();
+        let de = Deserializer::from_slice(&bytes).unwrap();
+        assert_eq!(
+            de.header(),
+            HeaderInfo {
+                version: 5,
+                flags: 0
+            }
+        );
+        assert!(!de.header().is_big_endian());
+
+        swap_endianness(&mut bytes).unwrap();
+        let mut de = Deserializer::from_reader(&bytes[..]).unwrap();
+        assert_eq!(de.header().flags, 0x80);
+        assert!(de.header().is_big_endian());
+        assert_eq!(i32::deserialize(&mut de).unwrap(), 1);
+        // The header stays available after decoding.
+        assert!(de.header().is_big_endian());
+    }
+
+    #[test]
+    fn phantom_fields() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Handle<T> {
+            id: u32,
+            #[serde(skip)]
+            skipped: PhantomData<T>,
+            marker: PhantomData<T>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Markers {
+            a: PhantomData<u8>,
+            b: PhantomData<String>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Empty {}
+
+        let handle = Handle::<String> {
+            id: 7,
+            skipped: PhantomData,
+            marker: PhantomData,
+        };
+        let bytes = to_vec(&handle).unwrap();
+        assert_eq!(from_slice::<Handle<String>>(&bytes).unwrap(), handle);
+
+        let markers = Markers {
+            a: PhantomData,
+            b: PhantomData,
+        };
+        let bytes = to_vec(&markers).unwrap();
+        assert_eq!(
+            bytes,
+            document(&[
+                0x0a, 0x00, 0x00, // root
+                0x0a, 0x01, 0x00, b'a', 0x00, // a
+                0x0a, 0x01, 0x00, b'b', 0x00, // b
+                0x00, // end of root
+                0x00, // end of document
+            ])
+        );
+        assert_eq!(from_slice::<Markers>(&bytes).unwrap(), markers);
+
+        let bytes = to_vec(&Empty {}).unwrap();
+        assert_eq!(bytes, document(&[0x0a, 0x00, 0x00, 0x00, 0x00]));
+        assert_eq!(from_slice::<Empty>(&bytes).unwrap(), Empty {});
+        assert_eq!(to_vec(&PhantomData::<u8>).unwrap(), bytes);
+    }
+
+    #[test]
+    fn mixed_compound_into_hash_map() {
+        #[derive(Serialize)]
+        struct Mixed {
+            byte: i8,
+            long: i64,
+            float: f32,
+            name: String,
+            data: ByteBuf,
+            heights: IntArray,
+            list: Vec<Vec<i16>>,
+            nested: Size,
+        }
+
+        let bytes = to_vec(&Mixed {
+            byte: -1,
+            long: 1 << 40,
+            float: 0.25,
+            name: "mixed".to_string(),
+            data: ByteBuf::from(vec![9, 8]),
+            heights: IntArray(vec![3, 4]),
+            list: vec![vec![1], vec![]],
+            nested: Size { w: 2, h: 3 },
+        })
+        .unwrap();
+        let fields = from_slice::<HashMap<String, Value>>(&bytes).unwrap();
+        assert_eq!(fields.len(), 8);
+        assert_eq!(fields["byte"], Value::Byte(-1));
+        assert_eq!(fields["long"], Value::Long(1 << 40));
+        assert_eq!(fields["float"], Value::Float(0.25));
+        assert_eq!(fields["name"], Value::String("mixed".to_string()));
+        assert_eq!(fields["data"], Value::ByteArray(vec![9, 8]));
+        // Value has no array variants, so an int array reads as a list of ints.
+        assert_eq!(
+            fields["heights"],
+            Value::List(vec![Value::Int(3), Value::Int(4)])
+        );
+        assert_eq!(
+            fields["list"],
+            Value::List(vec![
+                Value::List(vec![Value::Short(1)]),
+                Value::List(vec![])
+            ])
+        );
+        assert_eq!(
+            fields["nested"],
+            Value::compound()
+                .insert("w", 2i16)
+                .insert("h", 3i16)
+                .build()
+        );
+
+        let fields: BTreeMap<_, _> = fields.into_iter().collect();
+        let reencoded = to_vec(&fields).unwrap();
+        assert_eq!(
+            from_slice::<HashMap<String, Value>>(&reencoded)
+                .unwrap()
+                .len(),
+            8
+        );
+    }
+
+    #[test]
+    fn checked_trailer() {
+        let size = Size { w: 4, h: 5 };
+        let bytes = to_vec_checked(&size).unwrap();
+        let plain = to_vec(&size).unwrap();
+        assert_eq!(&bytes[..plain.len()], plain);
+        assert_eq!(bytes.len(), plain.len() + 8);
+        assert_eq!(from_slice_checked::<Size>(&bytes).unwrap(), size);
+
+        let truncated = &bytes[..bytes.len() - 3];
+        assert!(matches!(
+            from_slice_checked::<Size>(truncated),
+            Err(Error::MissingTrailer)
+        ));
+        assert!(matches!(
+            from_slice_checked::<Size>(&plain),
+            Err(Error::MissingTrailer)
+        ));
+
+        let mut corrupted = bytes.clone();
+        corrupted[plain.len() - 2] ^= 1;
+        assert!(matches!(
+            from_slice_checked::<Size>(&corrupted),
+            Err(Error::ChecksumMismatch(_, _))
+        ));
+    }
+
+    #[test]
+    fn parsed_keys() {
+        let counts: HashMap<i32, u8> = [(-3, 1), (0, 2), (70000, 3)].into_iter().collect();
+        let bytes = to_vec(&counts).unwrap();
+        assert_eq!(from_slice::<HashMap<i32, u8>>(&bytes).unwrap(), counts);
+        let initials: BTreeMap<char, bool> = [('a', true), ('\u{e9}', false)].into_iter().collect();
+        let bytes = to_vec(&initials).unwrap();
+        assert_eq!(
+            from_slice::<BTreeMap<char, bool>>(&bytes).unwrap(),
+            initials
+        );
+
+        let names: HashMap<String, u8> = [("seven".to_string(), 7)].into_iter().collect();
+        let bytes = to_vec(&names).unwrap();
+        let message = from_slice::<HashMap<i32, u8>>(&bytes)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(message, "compound key \"seven\" cannot be parsed as i32");
+        let big: HashMap<u16, u8> = [(300, 1)].into_iter().collect();
+        let bytes = to_vec(&big).unwrap();
+        assert!(from_slice::<HashMap<u8, u8>>(&bytes).is_err());
+    }
+
+    #[test]
+    fn array_adapters() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Heightmap {
+            #[serde(with = "crate::codec::int_array")]
+            heights: Vec<i32>,
+            #[serde(with = "crate::codec::long_array")]
+            states: Vec<i64>,
+        }
+
+        #[derive(Serialize)]
+        struct Wrapped {
+            heights: IntArray,
+            states: LongArray,
+        }
+
+        let map = Heightmap {
+            heights: vec![-1, 0, 1 << 20],
+            states: vec![i64::MIN],
+        };
+        let bytes = to_vec(&map).unwrap();
+        let wrapped = Wrapped {
+            heights: IntArray(map.heights.clone()),
+            states: LongArray(map.states.clone()),
+        };
+        assert_eq!(bytes, to_vec(&wrapped).unwrap());
+        assert_eq!(bytes[10], Tag::IntArray as u8);
+        assert_eq!(from_slice::<Heightmap>(&bytes).unwrap(), map);
+
+        let empty = Heightmap {
+            heights: vec![],
+            states: vec![],
+        };
+        let bytes = to_vec(&empty).unwrap();
+        assert_eq!(bytes[20..24], [0; 4]);
+        assert_eq!(from_slice::<Heightmap>(&bytes).unwrap(), empty);
+    }
+
+    #[test]
+    fn path_lookup() {
+        #[derive(Serialize)]
+        struct Player {
+            name: String,
+            pos: Vec<f64>,
+            heights: IntArray,
+            inventory: Vec<Size>,
+        }
+
+        let bytes = to_vec(&Player {
+            name: "Alex".to_string(),
+            pos: vec![1.5, 64.0, -2.0],
+            heights: IntArray(vec![7, 8, 9]),
+            inventory: vec![Size { w: 1, h: 2 }, Size { w: 3, h: 4 }],
+        })
+        .unwrap();
+        let get = |path: &[PathSeg]| get_path::<_, Value>(&bytes[..], path).unwrap();
+
+        assert_eq!(
+            get_path::<_, f64>(&bytes[..], &[PathSeg::Key("pos"), PathSeg::Index(1)]).unwrap(),
+            Some(64.0)
+        );
+        assert_eq!(
+            get_path::<_, String>(&bytes[..], &[PathSeg::Key("name")]).unwrap(),
+            Some("Alex".to_string())
+        );
+        assert_eq!(
+            get_path::<_, Size>(&bytes[..], &[PathSeg::Key("inventory"), PathSeg::Index(1)])
+                .unwrap(),
+            Some(Size { w: 3, h: 4 })
+        );
+        assert_eq!(
+            get(&[PathSeg::Key("heights"), PathSeg::Index(2)]),
+            Some(Value::Int(9))
+        );
+        assert_eq!(
+            get(&[
+                PathSeg::Key("inventory"),
+                PathSeg::Index(0),
+                PathSeg::Key("h")
+            ]),
+            Some(Value::Short(2))
+        );
+
+        // Paths that lead nowhere are not errors.
+        assert_eq!(get(&[PathSeg::Key("missing")]), None);
+        assert_eq!(get(&[PathSeg::Key("pos"), PathSeg::Index(3)]), None);
+        assert_eq!(get(&[PathSeg::Key("pos"), PathSeg::Key("x")]), None);
+        assert_eq!(get(&[PathSeg::Key("name"), PathSeg::Index(0)]), None);
+        // A value of the wrong type at the end of the path is.
+        assert!(get_path::<_, u8>(&bytes[..], &[PathSeg::Key("name")]).is_err());
+    }
+
+    #[test]
+    fn canonical_zeros() {
+        #[derive(Serialize, Deserialize)]
+        struct Floats {
+            zero: f32,
+            tiny: f32,
+            wide_tiny: f64,
+            normal: f64,
+        }
+
+        let floats = Floats {
+            zero: -0.0,
+            tiny: f32::from_bits(1),
+            wide_tiny: -f64::MIN_POSITIVE / 2.0,
+            normal: -1.5,
+        };
+        let encode = |canonical| {
+            let mut serializer = Serializer::new(Vec::new()).unwrap();
+            serializer.set_canonical_zeros(canonical);
+            floats.serialize(&mut serializer).unwrap();
+            let bytes = serializer.finish().unwrap();
+            from_slice::<Floats>(&bytes).unwrap()
+        };
+
+        let exact = encode(false);
+        assert_eq!(exact.zero.to_bits(), (-0.0f32).to_bits());
+        assert_eq!(exact.tiny.to_bits(), 1);
+        assert_eq!(exact.wide_tiny.to_bits(), floats.wide_tiny.to_bits());
+
+        let canonical = encode(true);
+        assert_eq!(canonical.zero.to_bits(), 0);
+        assert_eq!(canonical.tiny.to_bits(), 0);
+        assert_eq!(canonical.wide_tiny.to_bits(), 0);
+        assert_eq!(canonical.normal, -1.5);
+    }
+
+    #[test]
+    fn borrowed_list_elements() {
+        let bytes = to_vec(&["stone", "dirt", "\u{e9}t\u{e9}"]).unwrap();
+        let names = from_slice::<Vec<&str>>(&bytes).unwrap();
+        assert_eq!(names, ["stone", "dirt", "\u{e9}t\u{e9}"]);
+        let input = bytes.as_ptr_range();
+        assert!(names.iter().all(|name| input.contains(&name.as_ptr())));
+
+        let blobs = [Bytes::new(b"ab"), Bytes::new(b"")];
+        let bytes = to_vec(&blobs).unwrap();
+        let borrowed = from_slice::<Vec<&[u8]>>(&bytes).unwrap();
+        assert_eq!(borrowed, [&b"ab"[..], b""]);
+
+        // A null has to be decoded into a new buffer, so it can't be borrowed as a `&str`.
+        let bytes = to_vec(&["plain", "nul\0"]).unwrap();
+        assert!(from_slice::<Vec<&str>>(&bytes).is_err());
+        let names = from_slice::<Vec<Cow<str>>>(&bytes).unwrap();
+        assert_eq!(names, ["plain", "nul\0"]);
+    }
+
+    #[test]
+    fn results() {
+        for result in [Ok(-7), Err("timed out".to_string())] {
+            let bytes = to_vec(&result).unwrap();
+            assert_eq!(from_slice::<Result<i32, String>>(&bytes).unwrap(), result);
+        }
+
+        let bytes = to_vec(&Ok::<i32, String>(3)).unwrap();
+        assert_eq!(
+            from_slice::<Value>(&bytes).unwrap(),
+            Value::compound().insert("Ok", 3).build()
+        );
+
+        // `()` is an empty compound, so each variant holds one.
+        for result in [Ok(()), Err(())] {
+            let bytes = to_vec(&result).unwrap();
+            assert_eq!(from_slice::<Result<(), ()>>(&bytes).unwrap(), result);
+        }
+        let bytes = to_vec(&Err::<(), ()>(())).unwrap();
+        assert_eq!(
+            from_slice::<Value>(&bytes).unwrap(),
+            Value::compound()
+                .insert("Err", Value::compound().build())
+                .build()
+        );
+    }
+
+    #[test]
+    fn option_compounds() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Slot {
+            item: Option<String>,
+            count: Option<u8>,
+            nested: Option<Option<i32>>,
+            list: Vec<Option<i16>>,
+        }
+
+        let roundtrip = |slot: &Slot| {
+            let mut serializer = Serializer::new(Vec::new()).unwrap();
+            serializer.set_option_repr(OptionRepr::Compound);
+            slot.serialize(&mut serializer).unwrap();
+            let bytes = serializer.finish().unwrap();
+            let mut deserializer = Deserializer::from_slice(&bytes).unwrap();
+            deserializer.set_option_repr(OptionRepr::Compound);
+            assert_eq!(Slot::deserialize(&mut deserializer).unwrap(), *slot);
+            from_slice::<Value>(&bytes).unwrap()
+        };
+
+        let full = Slot {
+            item: Some("torch".to_string()),
+            count: Some(0),
+            nested: Some(Some(5)),
+            list: vec![Some(1), None],
+        };
+        let value = roundtrip(&full);
+        assert_eq!(
+            value,
+            Value::compound()
+                .insert("item", Value::compound().insert("Some", "torch").build())
+                .insert("count", Value::compound().insert("Some", 0i8).build())
+                .insert(
+                    "nested",
+                    Value::compound()
+                        .insert("Some", Value::compound().insert("Some", 5).build())
+                        .build()
+                )
+                .insert(
+                    "list",
+                    Value::List(vec![
+                        Value::compound().insert("Some", 1i16).build(),
+                        Value::compound().build(),
+                    ])
+                )
+                .build()
+        );
+
+        let empty = Slot {
+            item: None,
+            count: None,
+            nested: Some(None),
+            list: vec![None],
+        };
+        let value = roundtrip(&empty);
+        // Every field is present even when it holds `None`.
+        assert_eq!(
+            value,
+            Value::compound()
+                .insert("item", Value::compound().build())
+                .insert("count", Value::compound().build())
+                .insert(
+                    "nested",
+                    Value::compound()
+                        .insert("Some", Value::compound().build())
+                        .build()
+                )
+                .insert("list", Value::List(vec![Value::compound().build()]))
+                .build()
+        );
+    }
+
+    #[test]
+    fn ignored_root() {
+        let bytes = to_vec(&Size { w: 1, h: 2 }).unwrap();
+        let mut cursor = Cursor::new(&bytes);
+        from_reader::<_, IgnoredAny>(&mut cursor).unwrap();
+        // Everything up to the document terminator is consumed.
+        assert_eq!(cursor.position() as usize, bytes.len() - 1);
+
+        let bytes = to_vec(&vec!["a".to_string(), "b".to_string()]).unwrap();
+        let mut cursor = Cursor::new(&bytes);
+        from_reader::<_, IgnoredAny>(&mut cursor).unwrap();
+        assert_eq!(cursor.position() as usize, bytes.len() - 1);
+        from_slice::<IgnoredAny>(&to_vec(&7i64).unwrap()).unwrap();
+
+        let truncated = &bytes[..bytes.len() - 3];
+        assert!(matches!(
+            from_slice::<IgnoredAny>(truncated),
+            Err(Error::Eof)
+        ));
+        let mut bad = to_vec(&Size { w: 1, h: 2 }).unwrap();
+        bad[10] = 13;
+        assert!(matches!(
+            from_slice::<IgnoredAny>(&bad),
+            Err(Error::UnknownTag(13))
+        ));
+    }
+
+    #[test]
+    fn bitsets() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Grid {
+            #[serde(with = "crate::codec::bitset")]
+            cells: Vec<bool>,
+        }
+
+        #[derive(Serialize)]
+        struct Plain {
+            cells: Vec<bool>,
+        }
+
+        let cells: Vec<bool> = (0..13).map(|i| i % 3 == 0).collect();
+        let bytes = to_vec(&Grid {
+            cells: cells.clone(),
+        })
+        .unwrap();
+        // The byte array's length, 4 count bytes and 2 bytes of bits with only 5 of the second
+        // used, then the two terminators.
+        assert_eq!(
+            bytes[bytes.len() - 12..],
+            [6, 0, 0, 0, 13, 0, 0, 0, 0b0100_1001, 0b0001_0010, 0, 0]
+        );
+        assert_eq!(
+            from_slice::<Grid>(&bytes).unwrap(),
+            Grid {
+                cells: cells.clone()
+            }
+        );
+
+        let large: Vec<bool> = (0..800).map(|i| i % 7 == 0).collect();
+        let packed = to_vec(&Grid {
+            cells: large.clone(),
+        })
+        .unwrap();
+        let plain = to_vec(&Plain { cells: large }).unwrap();
+        // A list payload of 5 + 800 bytes against a byte array of 4 + 4 + 100.
+        assert_eq!(plain.len() - packed.len(), 805 - 108);
+
+        let empty = to_vec(&Grid { cells: vec![] }).unwrap();
+        assert_eq!(
+            from_slice::<Grid>(&empty).unwrap().cells,
+            Vec::<bool>::new()
+        );
+    }
+
+    #[test]
+    fn list_iteration() {
+        let numbers: Vec<i64> = (0..10_000).collect();
+        let bytes = to_vec(&numbers).unwrap();
+        let mut cursor = Cursor::new(&bytes);
+        let mut deserializer = Deserializer::from_reader(&mut cursor).unwrap();
+        let mut sum = 0;
+        for number in deserializer.list_iter::<i64>().unwrap() {
+            sum += number.unwrap();
+        }
+        assert_eq!(sum, numbers.iter().sum::<i64>());
+        drop(deserializer);
+        assert_eq!(cursor.position() as usize, bytes.len() - 1);
+
+        let bytes = to_vec(&LongArray(vec![4, 5])).unwrap();
+        let mut deserializer = Deserializer::from_slice(&bytes).unwrap();
+        let values: Vec<Value> = deserializer
+            .list_iter()
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(values, [Value::Long(4), Value::Long(5)]);
+
+        let bytes = to_vec(&["a", "b", "c"]).unwrap();
+        let mut deserializer = Deserializer::from_slice(&bytes).unwrap();
+        let mut iter = deserializer.list_iter::<u8>().unwrap();
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+
+        let bytes = to_vec(&Size { w: 1, h: 2 }).unwrap();
+        let mut deserializer = Deserializer::from_slice(&bytes).unwrap();
+        assert!(matches!(
+            deserializer.list_iter::<Value>(),
+            Err(Error::TagMismatch(Tag::List, Tag::Compound))
+        ));
+    }
+
+    /// Writes `count` elements into a sequence declared with `len`.
+    struct Declared {
+        len: Option<usize>,
+        count: u8,
+    }
+
+    impl Serialize for Declared {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::SerializeSeq;
+
+            let mut seq = serializer.serialize_seq(self.len)?;
+            for i in 0..self.count {
+                seq.serialize_element(&Evens(vec![i.into(), 2]))?;
+            }
+            seq.end()
+        }
+    }
+
+    #[test]
+    fn seq_lengths() {
+        let known = to_vec(&Declared {
+            len: Some(3),
+            count: 3,
+        })
+        .unwrap();
+        let unknown = to_vec(&Declared {
+            len: None,
+            count: 3,
+        })
+        .unwrap();
+        // The inner lists have unknown lengths too, and are held inside the outer one.
+        assert_eq!(known, unknown);
+        assert_eq!(
+            from_slice::<Vec<Vec<u32>>>(&unknown).unwrap(),
+            [vec![0, 2], vec![2], vec![2, 2]]
+        );
+        let empty = to_vec(&Declared {
+            len: None,
+            count: 0,
+        })
+        .unwrap();
+        assert_eq!(empty, to_vec(&Vec::<Vec<u32>>::new()).unwrap());
+
+        for (len, count) in [(3, 2), (1, 2), (0, 1)] {
+            let declared = Declared {
+                len: Some(len),
+                count,
+            };
+            assert!(matches!(
+                to_vec(&declared),
+                Err(Error::SeqLenMismatch(l, c)) if l == len && c == usize::from(count)
+            ));
+        }
+
+        struct Heights(Evens);
+
+        impl Serialize for Heights {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct(crate::array::INT_ARRAY, &self.0)
+            }
+        }
+
+        let bytes = to_vec(&Heights(Evens((0..7).collect()))).unwrap();
+        assert_eq!(bytes, to_vec(&IntArray(vec![0, 2, 4, 6])).unwrap());
+
+        // Held elements count towards the output limit as they are written.
+        let mut serializer = Serializer::new(Vec::new()).unwrap();
+        serializer.set_max_output(40);
+        assert!(matches!(
+            Evens((0..20).collect()).serialize(&mut serializer),
+            Err(Error::LimitExceeded(_, 40))
+        ));
+    }
+
+    #[test]
+    fn homogeneous_lists() {
+        /// A document whose root is a list with `tag` and `len`, followed by `elements`.
+        fn list(tag: Tag, len: u8, elements: &[u8]) -> Vec<u8> {
+            let mut body = vec![Tag::List as u8, 0, 0, tag as u8, len, 0, 0, 0];
+            body.extend_from_slice(elements);
+            body.push(0);
+            document(&body)
+        }
+
+        let shorts = vec![1u16, 0xFFFF];
+        let bytes = to_vec(&shorts).unwrap();
+        assert_eq!(bytes, list(Tag::Short, 2, &[1, 0, 0xFF, 0xFF]));
+        assert_eq!(from_slice::<Vec<u16>>(&bytes).unwrap(), shorts);
+
+        let signed = vec![-2i16, 3];
+        let bytes = to_vec(&signed).unwrap();
+        assert_eq!(bytes, list(Tag::Short, 2, &[0xFE, 0xFF, 3, 0]));
+        assert_eq!(from_slice::<Vec<i16>>(&bytes).unwrap(), signed);
+
+        let floats = vec![1.0f32, -0.5];
+        let bytes = to_vec(&floats).unwrap();
+        assert_eq!(
+            bytes,
+            list(Tag::Float, 2, &[0, 0, 0x80, 0x3F, 0, 0, 0, 0xBF])
+        );
+        assert_eq!(from_slice::<Vec<f32>>(&bytes).unwrap(), floats);
+
+        // Each string keeps its own length prefix, while the tag is shared.
+        let strings = vec!["ab".to_string(), String::new(), "c".to_string()];
+        let bytes = to_vec(&strings).unwrap();
+        assert_eq!(
+            bytes,
+            list(Tag::String, 3, &[2, 0, b'a', b'b', 0, 0, 1, 0, b'c'])
+        );
+        assert_eq!(from_slice::<Vec<String>>(&bytes).unwrap(), strings);
+    }
+
+    #[test]
+    fn max_fields() {
+        let map: BTreeMap<String, i8> = (0..5).map(|i| (format!("f{i}"), i)).collect();
+        let bytes = to_vec(&map).unwrap();
+        let decode = |max| {
+            let mut de = Deserializer::from_slice(&bytes).unwrap();
+            de.set_max_fields(max);
+            HashMap::<String, i8>::deserialize(&mut de)
+        };
+        assert_eq!(decode(5).unwrap().len(), 5);
+        assert!(matches!(decode(4), Err(Error::LimitExceeded(5, 4))));
+
+        // Each compound is counted separately.
+        let nested: BTreeMap<String, Size> = (0..3)
+            .map(|i| (format!("s{i}"), Size { w: i, h: i }))
+            .collect();
+        let bytes = to_vec(&nested).unwrap();
+        let mut de = Deserializer::from_slice(&bytes).unwrap();
+        de.set_max_fields(3);
+        assert_eq!(
+            BTreeMap::<String, Size>::deserialize(&mut de).unwrap(),
+            nested
+        );
+        let mut de = Deserializer::from_slice(&bytes).unwrap();
+        de.set_max_fields(1);
+        assert!(matches!(
+            BTreeMap::<String, Size>::deserialize(&mut de),
+            Err(Error::LimitExceeded(2, 1))
+        ));
+    }
+
+    #[test]
+    fn non_zero_integers() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Entity {
+            id: NonZeroU32,
+            owner: Option<NonZeroI64>,
+        }
+
+        #[derive(Serialize)]
+        struct RawEntity {
+            id: u32,
+            owner: Option<i64>,
+        }
+
+        let entity = Entity {
+            id: NonZeroU32::new(42).unwrap(),
+            owner: NonZeroI64::new(-9),
+        };
+        let bytes = to_vec(&entity).unwrap();
+        assert_eq!(
+            bytes,
+            to_vec(&RawEntity {
+                id: 42,
+                owner: Some(-9)
+            })
+            .unwrap()
+        );
+        assert_eq!(from_slice::<Entity>(&bytes).unwrap(), entity);
+
+        let bytes = to_vec(&RawEntity { id: 0, owner: None }).unwrap();
+        let err = from_slice::<Entity>(&bytes).unwrap_err();
+        assert!(err.to_string().contains("expected a nonzero u32"), "{err}");
+
+        // A missing field is `None`, while a zero is still rejected inside `Some`.
+        let bytes = to_vec(&RawEntity { id: 1, owner: None }).unwrap();
+        assert_eq!(from_slice::<Entity>(&bytes).unwrap().owner, None);
+        let bytes = to_vec(&RawEntity {
+            id: 1,
+            owner: Some(0),
+        })
+        .unwrap();
+        let err = from_slice::<Entity>(&bytes).unwrap_err();
+        assert!(err.to_string().contains("expected a nonzero i64"), "{err}");
+    }
+
+    #[test]
+    fn structural_diff() {
+        #[derive(Serialize)]
+        struct Player {
+            name: &'static str,
+            pos: Vec<f64>,
+            size: Size,
+            level: Option<u8>,
+        }
+
+        let old = to_vec(&Player {
+            name: "Alex",
+            pos: vec![1.0, 2.0, f64::NAN],
+            size: Size { w: 1, h: 2 },
+            level: None,
+        })
+        .unwrap();
+        assert_eq!(debug::diff(&old, &old).unwrap(), []);
+
+        let new = to_vec(&Player {
+            name: "Alex",
+            pos: vec![1.0, -2.0, f64::NAN, 4.0],
+            size: Size { w: 1, h: 3 },
+            level: Some(5),
+        })
+        .unwrap();
+        let diffs = debug::diff(&old, &new).unwrap();
+        let summary: Vec<_> = diffs
+            .iter()
+            .map(|d| (d.path.join("/"), d.old.clone(), d.new.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("level".to_string(), None, Some(Value::Byte(5))),
+                (
+                    "pos/1".to_string(),
+                    Some(Value::Double(2.0)),
+                    Some(Value::Double(-2.0))
+                ),
+                ("pos/3".to_string(), None, Some(Value::Double(4.0))),
+                (
+                    "size/h".to_string(),
+                    Some(Value::Short(2)),
+                    Some(Value::Short(3))
+                ),
+            ]
+        );
+
+        let diffs = debug::diff(&old, &to_vec(&7).unwrap()).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert!(diffs[0].path.is_empty());
+        assert_eq!(diffs[0].new, Some(Value::Int(7)));
+    }
+
+    #[test]
+    fn large_arrays() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Tables {
+            #[serde(with = "crate::codec::array")]
+            light: [i32; 100],
+            #[serde(with = "crate::codec::byte_array")]
+            palette: [u8; 256],
+        }
+
+        #[derive(Serialize)]
+        struct Plain {
+            light: Vec<i32>,
+            palette: ByteBuf,
+        }
+
+        let mut palette = [0; 256];
+        for (i, byte) in palette.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let tables = Tables {
+            light: std::array::from_fn(|i| i as i32 - 50),
+            palette,
+        };
+        let bytes = to_vec(&tables).unwrap();
+        let plain = Plain {
+            light: tables.light.to_vec(),
+            palette: ByteBuf::from(palette.to_vec()),
+        };
+        assert_eq!(bytes, to_vec(&plain).unwrap());
+        assert_eq!(from_slice::<Tables>(&bytes).unwrap(), tables);
+
+        // The array follows the tuple layout, so it becomes a compound keyed by index.
+        let mut bytes = Vec::new();
+        let mut ser = Serializer::new(&mut bytes).unwrap();
+        ser.set_tuple_repr(TupleRepr::Compound);
+        tables.serialize(&mut ser).unwrap();
+        ser.finish().unwrap();
+        let Value::Compound(fields) = from_slice::<Value>(&bytes).unwrap() else {
+            panic!("expected a compound");
+        };
+        assert_eq!(fields["light"].tag(), Tag::Compound);
+        let mut de = Deserializer::from_slice(&bytes).unwrap();
+        de.set_tuple_repr(TupleRepr::Compound);
+        assert_eq!(Tables::deserialize(&mut de).unwrap(), tables);
+
+        let short = Plain {
+            light: vec![0; 99],
+            palette: ByteBuf::from(palette.to_vec()),
+        };
+        assert!(from_slice::<Tables>(&to_vec(&short).unwrap()).is_err());
+        let short = Plain {
+            light: vec![0; 100],
+            palette: ByteBuf::from(vec![0; 255]),
+        };
+        let err = from_slice::<Tables>(&to_vec(&short).unwrap()).unwrap_err();
+        assert!(
+            err.to_string().contains("a byte array of length 256"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn interned_names() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Block {
+            position: Size,
+            name: String,
+            lit: bool,
+        }
+
+        let blocks: Vec<Block> = (0..1000)
+            .map(|i| Block {
+                position: Size { w: i, h: i * 2 },
+                name: format!("block{}", i % 4),
+                lit: i % 2 == 0,
+            })
+            .collect();
+        let plain = to_vec(&blocks).unwrap();
+        let mut serializer = Serializer::new_interned(Vec::new()).unwrap();
+        blocks.serialize(&mut serializer).unwrap();
+        let interned = serializer.finish().unwrap();
+        // Each block saves the bytes of its five names, less two bytes for each reference.
+        let names = ["position", "w", "h", "name", "lit"];
+        let saved: usize = names.iter().map(|name| name.len()).sum();
+        assert_eq!(plain.len() - interned.len(), 999 * saved);
+
+        assert_eq!(interned[5], 6);
+        assert!(read_header(&interned[..]).unwrap().has_interned_names());
+        assert_eq!(from_slice::<Vec<Block>>(&interned).unwrap(), blocks);
+        assert_eq!(
+            from_slice::<Value>(&interned).unwrap(),
+            from_slice::<Value>(&plain).unwrap()
+        );
+        from_slice::<IgnoredAny>(&interned).unwrap();
+        assert_eq!(
+            get_path::<_, String>(&interned[..], &[PathSeg::Index(2), PathSeg::Key("name")])
+                .unwrap(),
+            Some("block2".to_string())
+        );
+
+        let mut swapped = interned.clone();
+        swap_endianness(&mut swapped).unwrap();
+        assert_eq!(from_slice::<Vec<Block>>(&swapped).unwrap(), blocks);
+        swap_endianness(&mut swapped).unwrap();
+        assert_eq!(swapped, interned);
+
+        assert!(matches!(
+            patch_field(&mut swapped, &["0", "lit"], Value::Byte(0)),
+            Err(Error::InternedPatch)
+        ));
+
+        // A reference to a name that was never defined.
+        let mut bad = document(&[0x0a, 0x00, 0x00, 0x01, 0x03, 0x80, 0x01, 0x00, 0x00]);
+        bad[5] = 6;
+        assert!(matches!(
+            from_slice::<Value>(&bad),
+            Err(Error::UnknownName(3))
+        ));
+    }
+
+    #[test]
+    fn signed_extremes() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Signed {
+            byte: i8,
+            short: i16,
+            int: i32,
+            long: i64,
+        }
+
+        for signed in [
+            Signed {
+                byte: -1,
+                short: -1,
+                int: -1,
+                long: -1,
+            },
+            Signed {
+                byte: i8::MIN,
+                short: i16::MIN,
+                int: i32::MIN,
+                long: i64::MIN,
+            },
+        ] {
+            let bytes = to_vec(&signed).unwrap();
+            assert_eq!(from_slice::<Signed>(&bytes).unwrap(), signed);
+        }
+
+        // Each value is its two's complement, which reads back as the unsigned maximum.
+        let bytes = to_vec(&i8::MIN).unwrap();
+        assert_eq!(bytes, document(&[0x01, 0x00, 0x00, 0x80, 0x00]));
+        assert_eq!(from_slice::<i8>(&bytes).unwrap(), i8::MIN);
+        assert_eq!(from_slice::<u8>(&bytes).unwrap(), 0x80);
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), Value::Byte(i8::MIN));
+        let bytes = to_vec(&-1i64).unwrap();
+        assert_eq!(from_slice::<u64>(&bytes).unwrap(), u64::MAX);
+        assert_eq!(from_slice::<i64>(&bytes).unwrap(), -1);
+        assert_eq!(
+            from_slice::<i16>(&to_vec(&i16::MIN).unwrap()).unwrap(),
+            i16::MIN
+        );
+        assert_eq!(
+            from_slice::<i32>(&to_vec(&i32::MIN).unwrap()).unwrap(),
+            i32::MIN
+        );
+    }
+
+    #[test]
+    fn validation() {
+        let mut nested = Value::compound().insert("leaf", "x").build();
+        for _ in 0..10 {
+            nested = Value::compound()
+                .insert("inner", Value::List(vec![nested]))
+                .build();
+        }
+        let bytes = to_vec(&nested).unwrap();
+        validate(&bytes).unwrap();
+        validate(&to_vec(&Option::<u8>::None).unwrap()).unwrap();
+
+        for len in [bytes.len() - 1, bytes.len() / 2, 8] {
+            assert!(matches!(validate(&bytes[..len]), Err(Error::Eof)));
+        }
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(validate(&trailing), Err(Error::TrailingBytes)));
+
+        // A string whose length runs past the end of the input.
+        let overrun = document(&[0x08, 0x00, 0x00, 0xFF, 0x00, b'a', 0x00]);
+        assert!(matches!(validate(&overrun), Err(Error::Eof)));
+        // A list of ints claiming far more elements than there are bytes.
+        let overrun = document(&[0x09, 0x00, 0x00, 0x03, 0xFF, 0xFF, 0xFF, 0x7F, 0x00]);
+        assert!(matches!(validate(&overrun), Err(Error::Eof)));
+
+        let mut deserializer = Deserializer::from_slice(&bytes).unwrap();
+        // The root and ten lists each holding a compound.
+        deserializer.set_max_depth(21);
+        deserializer.validate().unwrap();
+        let mut deserializer = Deserializer::from_slice(&bytes).unwrap();
+        deserializer.set_max_depth(20);
+        assert!(matches!(deserializer.validate(), Err(Error::TooDeep(20))));
+        let mut deserializer = Deserializer::from_slice(&bytes).unwrap();
+        deserializer.set_max_depth(20);
+        assert!(matches!(
+            Value::deserialize(&mut deserializer),
+            Err(Error::TooDeep(20))
+        ));
+        let mut deserializer = Deserializer::from_slice(&bytes).unwrap();
+        // Names count towards the string limit, so "inner" is the first to exceed it.
+        deserializer.set_max_string_len(4);
+        assert!(matches!(
+            deserializer.validate(),
+            Err(Error::LimitExceeded(5, 4))
+        ));
+
+        // Each variant compound is a level, so recursive enums are bounded too.
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        enum Tree {
+            Leaf,
+            Node(Box<Tree>),
+        }
+        let tree = |depth: usize| {
+            let mut doc = document(&[0x0a, 0x00, 0x00]);
+            for _ in 1..depth {
+                doc.extend_from_slice(&[0x0a, 0x04, 0x00, b'N', b'o', b'd', b'e']);
+            }
+            doc.extend_from_slice(&[0x08, 0x04, 0x00, b'N', b'o', b'd', b'e']);
+            doc.extend_from_slice(&[0x04, 0x00, b'L', b'e', b'a', b'f']);
+            doc.resize(doc.len() + depth + 1, 0x00);
+            doc
+        };
+        from_slice::<Tree>(&tree(10)).unwrap();
+        for depth in [600, 200_000] {
+            assert!(matches!(
+                from_slice::<Tree>(&tree(depth)),
+                Err(Error::TooDeep(512))
+            ));
+        }
+        let doc = tree(10);
+        let mut deserializer = Deserializer::from_slice(&doc).unwrap();
+        deserializer.set_max_depth(9);
+        assert!(matches!(
+            Tree::deserialize(&mut deserializer),
+            Err(Error::TooDeep(9))
+        ));
+    }
+
+    #[test]
+    fn renamed_fields() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Spawner {
+            spawn_count: i16,
+            max_nearby_entities: i16,
+            #[serde(rename = "SpawnData")]
+            spawn_data: String,
+        }
+
+        let spawner = Spawner {
+            spawn_count: 4,
+            max_nearby_entities: 6,
+            spawn_data: "zombie".to_string(),
+        };
+        let bytes = to_vec(&spawner).unwrap();
+        let mut expected = vec![0x0a, 0x00, 0x00];
+        expected.extend_from_slice(b"\x02\x0a\x00spawnCount\x04\x00");
+        expected.extend_from_slice(b"\x02\x11\x00maxNearbyEntities\x06\x00");
+        expected.extend_from_slice(b"\x08\x09\x00SpawnData\x06\x00zombie");
+        expected.extend_from_slice(&[0x00, 0x00]);
+        assert_eq!(bytes, document(&expected));
+        assert_eq!(from_slice::<Spawner>(&bytes).unwrap(), spawner);
+        assert_eq!(
+            root_keys(&bytes[..]).unwrap(),
+            ["spawnCount", "maxNearbyEntities", "SpawnData"]
+        );
+    }
+
+    #[test]
+    fn tee_output() {
+        let mut first = Vec::new();
+        let mut second = Vec::new();
+        to_writer(tee(&mut first, &mut second), &Size { w: 3, h: 4 }).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first, to_vec(&Size { w: 3, h: 4 }).unwrap());
+
+        struct Broken;
+
+        impl std::io::Write for Broken {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut first = Vec::new();
+        let err = to_writer(tee(&mut first, Broken), &Size { w: 3, h: 4 }).unwrap_err();
+        assert!(matches!(err, Error::Io(_)), "{err}");
+        assert!(!first.is_empty());
+    }
+
+    #[test]
+    fn skipped_fields() {
+        #[derive(Serialize)]
+        struct Entity {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            a: Option<i8>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            b: Option<i8>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            c: Option<i8>,
+        }
+
+        let cases = [
+            (
+                (Some(1), Some(2), Some(3)),
+                &b"\x01\x01\x00a\x01\x01\x01\x00b\x02\x01\x01\x00c\x03"[..],
+            ),
+            (
+                (None, Some(2), Some(3)),
+                b"\x01\x01\x00b\x02\x01\x01\x00c\x03",
+            ),
+            (
+                (Some(1), None, Some(3)),
+                b"\x01\x01\x00a\x01\x01\x01\x00c\x03",
+            ),
+            (
+                (Some(1), Some(2), None),
+                b"\x01\x01\x00a\x01\x01\x01\x00b\x02",
+            ),
+            ((None, None, None), b""),
+        ];
+        for ((a, b, c), fields) in cases {
+            let mut expected = vec![0x0a, 0x00, 0x00];
+            expected.extend_from_slice(fields);
+            expected.extend_from_slice(&[0x00, 0x00]);
+            let entity = Entity { a, b, c };
+            let bytes = to_vec(&entity).unwrap();
+            assert_eq!(bytes, document(&expected));
+            assert_eq!(to_vec(&entity).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn counted_reads() {
+        let mut stream = to_vec(&Size { w: 3, h: 4 }).unwrap();
+        let first = stream.len();
+        stream.extend(to_vec(&vec![String::from("tail")]).unwrap());
+
+        let mut reader = &stream[..];
+        let (size, read) = from_reader_counted::<_, Size>(&mut reader).unwrap();
+        assert_eq!(size, Size { w: 3, h: 4 });
+        assert_eq!(read, first);
+        let (tail, read) = from_reader_counted::<_, Vec<String>>(&mut reader).unwrap();
+        assert_eq!(tail, ["tail"]);
+        assert_eq!(read, stream.len() - first);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn sorted_map_keys() {
+        let keys = (0..64).map(|i| format!("key{i}"));
+        let sorted: BTreeMap<String, Vec<i32>> = keys.map(|k| (k, vec![1, 2])).collect();
+        let encode = |map: &HashMap<String, Vec<i32>>| {
+            let mut serializer = Serializer::new(Vec::new()).unwrap();
+            serializer.set_sort_map_keys(true);
+            map.serialize(&mut serializer).unwrap();
+            serializer.finish().unwrap()
+        };
+
+        let first: HashMap<_, _> = sorted.clone().into_iter().collect();
+        let second: HashMap<_, _> = sorted.clone().into_iter().rev().collect();
+        let bytes = encode(&first);
+        assert_eq!(bytes, encode(&second));
+        assert_eq!(bytes, to_vec(&sorted).unwrap());
+        assert_eq!(
+            from_slice::<HashMap<String, Vec<i32>>>(&bytes).unwrap(),
+            first
+        );
+
+        let mut serializer = Serializer::new_interned(Vec::new()).unwrap();
+        serializer.set_sort_map_keys(true);
+        let err = first.serialize(&mut serializer).unwrap_err();
+        assert!(matches!(err, Error::InternedSort), "{err}");
+
+        // A struct with flattened fields is written as a map, so its named fields are sorted
+        // along with the flattened ones.
+        #[derive(Serialize)]
+        struct Flattened {
+            z: i8,
+            #[serde(flatten)]
+            extra: BTreeMap<String, i8>,
+        }
+        let flattened = Flattened {
+            z: 1,
+            extra: BTreeMap::from([("a".to_string(), 2)]),
+        };
+        let mut serializer = Serializer::new(Vec::new()).unwrap();
+        serializer.set_sort_map_keys(true);
+        flattened.serialize(&mut serializer).unwrap();
+        assert_eq!(
+            serializer.finish().unwrap(),
+            to_vec(&BTreeMap::from([("a", 2i8), ("z", 1)])).unwrap()
+        );
+        let mut serializer = Serializer::new_interned(Vec::new()).unwrap();
+        serializer.set_sort_map_keys(true);
+        let err = flattened.serialize(&mut serializer).unwrap_err();
+        assert!(matches!(err, Error::InternedSort), "{err}");
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn byte_array_lengths() {
+        // Zeroed allocations are mapped lazily, and neither length is ever written out, so
+        // these cost address space rather than memory.
+        let largest = vec![0u8; i32::MAX as usize];
+        let stats = dry_run(Bytes::new(&largest)).unwrap();
+        assert_eq!(stats.total_bytes, HEADER.len() + 3 + 4 + largest.len() + 1);
+        drop(largest);
+
+        let too_long = vec![0u8; i32::MAX as usize + 1];
+        let err = dry_run(Bytes::new(&too_long)).unwrap_err();
+        assert!(
+            matches!(err, Error::BytesLen(len) if len == too_long.len()),
+            "{err}"
+        );
+        let err = to_buf(&mut [0; 16], Bytes::new(&too_long)).unwrap_err();
+        assert!(matches!(err, Error::BytesLen(_)), "{err}");
+    }
+
+    #[test]
+    fn custom_tags() {
+        fn color(input: &mut dyn std::io::Read) -> crate::Result<Value> {
+            let mut rgb = [0; 3];
+            input.read_exact(&mut rgb)?;
+            Ok(Value::ByteArray(rgb.to_vec()))
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Banner {
+            name: String,
+            color: Value,
+        }
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Named {
+            name: String,
+        }
+        #[derive(Deserialize, Debug)]
+        struct Typed {
+            #[allow(dead_code)]
+            color: i32,
+        }
+
+        let mut body = vec![0x0a, 0x00, 0x00];
+        body.extend_from_slice(b"\x40\x05\x00color\x10\x20\x30");
+        body.extend_from_slice(b"\x08\x04\x00name\x01\x00x");
+        body.extend_from_slice(&[0x00, 0x00]);
+        let bytes = document(&body);
+        let decode = |f: fn(&mut Deserializer<SliceRead>) -> crate::Result<()>| {
+            let mut de = Deserializer::from_slice(&bytes).unwrap();
+            de.register_tag(0x40, color);
+            f(&mut de)
+        };
+
+        let err = from_slice::<Value>(&bytes).unwrap_err();
+        assert!(matches!(err, Error::UnknownTag(0x40)), "{err}");
+        decode(|de| {
+            let value = Value::deserialize(de)?;
+            let expected = Value::compound()
+                .insert("color", Value::ByteArray(vec![0x10, 0x20, 0x30]))
+                .insert("name", "x")
+                .build();
+            assert_eq!(value, expected);
+            Ok(())
+        })
+        .unwrap();
+        decode(|de| {
+            let banner = Banner::deserialize(de)?;
+            assert_eq!(banner.color, Value::ByteArray(vec![0x10, 0x20, 0x30]));
+            Ok(())
+        })
+        .unwrap();
+        decode(|de| {
+            assert_eq!(Named::deserialize(de)?.name, "x");
+            Ok(())
+        })
+        .unwrap();
+        decode(|de| de.validate()).unwrap();
+        let err = decode(|de| Typed::deserialize(de).map(drop)).unwrap_err();
+        assert!(matches!(err, Error::UnknownTag(0x40)), "{err}");
+    }
+
+    #[test]
+    fn mutf8_name_lengths() {
+        let names = BTreeMap::from([("a\0b", 1i8), ("\u{1F600}", 2)]);
+        let bytes = to_vec(&names).unwrap();
+        let mut expected = vec![0x0a, 0x00, 0x00];
+        expected.extend_from_slice(b"\x01\x04\x00a\xc0\x80b\x01");
+        expected.extend_from_slice(b"\x01\x06\x00\xed\xa0\xbd\xed\xb8\x80\x02");
+        expected.extend_from_slice(&[0x00, 0x00]);
+        assert_eq!(bytes, document(&expected));
+        assert_eq!(
+            from_slice::<BTreeMap<String, i8>>(&bytes).unwrap(),
+            names.into_iter().map(|(k, v)| (k.to_owned(), v)).collect()
+        );
+
+        // 40000 bytes of UTF-8, but 80000 once each null takes two bytes.
+        let nulls = BTreeMap::from([("\0".repeat(40_000), 0i8)]);
+        let err = to_vec(&nulls).unwrap_err();
+        assert!(matches!(err, Error::StrLen(80_000)), "{err}");
+
+        let mut serializer = Serializer::new_interned(Vec::new()).unwrap();
+        let long = BTreeMap::from([("\0".repeat(20_000), 0i8)]);
+        let err = long.serialize(&mut serializer).unwrap_err();
+        assert!(matches!(err, Error::LimitExceeded(40_000, 0x7fff)), "{err}");
+    }
+
+    #[test]
+    fn strict_bools() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Flag {
+            on: bool,
+        }
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Count {
+            on: u8,
+        }
+
+        for (byte, lenient) in [(0, false), (1, true), (2, true)] {
+            let bytes = document(&[
+                0x0a, 0x00, 0x00, 0x01, 0x02, 0x00, b'o', b'n', byte, 0x00, 0x00,
+            ]);
+            assert_eq!(from_slice::<Flag>(&bytes).unwrap(), Flag { on: lenient });
+            let mut de = Deserializer::from_slice(&bytes).unwrap();
+            de.set_strict_bools(true);
+            let strict = Flag::deserialize(&mut de);
+            if byte < 2 {
+                assert_eq!(strict.unwrap(), Flag { on: lenient });
+            } else {
+                let err = strict.unwrap_err();
+                assert!(matches!(err, Error::InvalidBool(2)), "{err}");
+            }
+            let mut de = Deserializer::from_slice(&bytes).unwrap();
+            de.set_strict_bools(true);
+            assert_eq!(Count::deserialize(&mut de).unwrap(), Count { on: byte });
+        }
+    }
+
+    #[test]
+    fn tuple_arities() {
+        // The standard library only compares and prints tuples of up to 12 elements, so the
+        // decoded tuple is checked by encoding it again.
+        fn round_trip<T: Serialize + DeserializeOwned>(tuple: T, len: u8) {
+            let bytes = to_vec(&tuple).unwrap();
+            let list = HEADER.len() + 3;
+            assert_eq!(bytes[HEADER.len()], Tag::List as u8);
+            assert_eq!(bytes[list..list + 5], [Tag::Int as u8, len, 0, 0, 0]);
+            assert_eq!(bytes.len(), list + 5 + 4 * usize::from(len) + 1);
+            assert_eq!(to_vec(&from_slice::<T>(&bytes).unwrap()).unwrap(), bytes);
+        }
+
+        round_trip((7,), 1);
+        round_trip((1, 2), 2);
+        round_trip((1, 2, 3), 3);
+        round_trip((1, 2, 3, 4, 5, 6, 7, 8), 8);
+        round_trip((1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16), 16);
+
+        let pair = to_vec(&(1, 2)).unwrap();
+        let err = from_slice::<(i32, i32, i32)>(&pair).unwrap_err();
+        assert!(err.to_string().contains("length 2"), "{err}");
+        let triple = to_vec(&(1, 2, 3)).unwrap();
+        assert!(from_slice::<(i32, i32)>(&triple).is_err());
+    }
+
+    #[test]
+    fn merge_patches() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Window {
+            title: String,
+            size: Size,
+            visible: bool,
+            tags: Vec<String>,
+        }
+
+        let mut window = Window {
+            title: "main".to_string(),
+            size: Size { w: 640, h: 480 },
+            visible: true,
+            tags: vec!["a".to_string(), "b".to_string()],
+        };
+        let patch = Value::compound()
+            .insert("size", Value::compound().insert("h", 600i16).build())
+            .insert("tags", Value::list(vec!["c"]).unwrap())
+            .build();
+        merge_from_slice(&mut window, &to_vec(&patch).unwrap()).unwrap();
+        assert_eq!(
+            window,
+            Window {
+                title: "main".to_string(),
+                size: Size { w: 640, h: 600 },
+                visible: true,
+                tags: vec!["c".to_string()],
+            }
+        );
+
+        let patch = Value::compound().insert("title", 5i32).build();
+        let err = merge_from_slice(&mut window, &to_vec(&patch).unwrap()).unwrap_err();
+        assert!(
+            matches!(err, Error::TagMismatch(Tag::String, Tag::Int)),
+            "{err}"
+        );
+        assert_eq!(window.title, "main");
+
+        // Array tags survive the merge, in the target and in a big-endian patch alike.
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Chunk {
+            ints: IntArray,
+            #[serde(with = "crate::codec::int_array")]
+            raw: Vec<i32>,
+            n: i32,
+        }
+        let mut chunk = Chunk {
+            ints: IntArray(vec![1, 2]),
+            raw: vec![3],
+            n: 4,
+        };
+        let patch = Value::compound().insert("n", 5i32).build();
+        merge_from_slice(&mut chunk, &to_vec(&patch).unwrap()).unwrap();
+        assert_eq!(
+            chunk,
+            Chunk {
+                ints: IntArray(vec![1, 2]),
+                raw: vec![3],
+                n: 5,
+            }
+        );
+        let mut patch = to_vec(&BTreeMap::from([("raw", IntArray(vec![7, 8]))])).unwrap();
+        swap_endianness(&mut patch).unwrap();
+        merge_from_slice(&mut chunk, &patch).unwrap();
+        assert_eq!(chunk.raw, [7, 8]);
+
+        let mut serializer = Serializer::new_interned(Vec::new()).unwrap();
+        BTreeMap::from([("n", 6i32)])
+            .serialize(&mut serializer)
+            .unwrap();
+        let patch = serializer.finish().unwrap();
+        let err = merge_from_slice(&mut chunk, &patch).unwrap_err();
+        assert!(matches!(err, Error::InternedPatch), "{err}");
+        assert_eq!(chunk.n, 5);
+    }
+
+    #[test]
+    fn owned_strings_from_readers() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Sign {
+            text: String,
+        }
+        #[derive(Deserialize, Debug)]
+        struct Borrowed<'a> {
+            #[allow(dead_code)]
+            text: &'a str,
+        }
+
+        let sign = Sign {
+            text: "line\n".repeat(12_000),
+        };
+        let bytes = to_vec(&sign).unwrap();
+        assert_eq!(from_reader::<_, Sign>(&bytes[..]).unwrap(), sign);
+        let text = from_reader::<_, BTreeMap<String, Cow<str>>>(&bytes[..]).unwrap();
+        assert!(matches!(&text["text"], Cow::Owned(text) if *text == sign.text));
+
+        let mut de = Deserializer::from_reader(&bytes[..]).unwrap();
+        let err = Borrowed::deserialize(&mut de).unwrap_err();
+        assert!(err.to_string().contains("borrowed string"), "{err}");
+    }
+
+    #[test]
+    fn compact_list_lengths() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Entity {
+            pos: (f64, f64, f64),
+            motion: Vec<f64>,
+            rotation: [f32; 2],
+        }
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Chunk {
+            entities: Vec<Entity>,
+            heights: Vec<i16>,
+            empty: Vec<i8>,
+        }
+
+        let chunk = Chunk {
+            entities: (0..1000)
+                .map(|i| Entity {
+                    pos: (i as f64, 64.0, -(i as f64)),
+                    motion: vec![0.0, -0.08, 0.0],
+                    rotation: [90.0, 0.0],
+                })
+                .collect(),
+            heights: (0..300).collect(),
+            empty: Vec::new(),
+        };
+        let encode = |chunk: &Chunk| {
+            let mut serializer = Serializer::new_compact_lists(Vec::new()).unwrap();
+            chunk.serialize(&mut serializer).unwrap();
+            serializer.finish().unwrap()
+        };
+        let compact = encode(&chunk);
+        let standard = to_vec(&chunk).unwrap();
+        assert_eq!(compact[5], 7);
+        assert!(read_header(&compact[..]).unwrap().has_compact_lists());
+        // Every list of up to 127 elements saves three bytes. The entity list and the 300
+        // heights need two bytes, saving two each.
+        assert_eq!(standard.len() - compact.len(), 3 * 1000 * 3 + 3 + 2 * 2);
+
+        assert_eq!(from_slice::<Chunk>(&compact).unwrap(), chunk);
+        assert_eq!(from_reader::<_, Chunk>(&compact[..]).unwrap(), chunk);
+        validate(&compact).unwrap();
+        assert_eq!(
+            get_path::<_, i16>(
+                &compact[..],
+                &[PathSeg::Key("heights"), PathSeg::Index(299)]
+            )
+            .unwrap(),
+            Some(299)
+        );
+        assert_eq!(
+            from_slice::<Value>(&compact).unwrap(),
+            from_slice::<Value>(&standard).unwrap()
+        );
+
+        let mut swapped = compact.clone();
+        swap_endianness(&mut swapped).unwrap();
+        assert_eq!(from_slice::<Chunk>(&swapped).unwrap(), chunk);
+
+        let mut buf = compact.clone();
+        let err = patch_field(&mut buf, &["empty"], Value::ByteArray(vec![])).unwrap_err();
+        assert!(matches!(err, Error::CompactPatch), "{err}");
+
+        let mut serializer =
+            Serializer::new_compact_lists(std::io::Cursor::new(Vec::new())).unwrap();
+        serializer
+            .serialize_list_from_iter_seekable("", (0..200).map(|i| i as i16))
+            .unwrap();
+        let bytes = serializer.finish().unwrap().into_inner();
+        assert_eq!(
+            bytes[10..16],
+            [Tag::Short as u8, 0xc8, 0x81, 0x80, 0x80, 0x00]
+        );
+        assert_eq!(
+            from_slice::<Vec<i16>>(&bytes).unwrap(),
+            (0..200).collect::<Vec<_>>()
+        );
+
+        let mut body = document(&[0x09, 0x00, 0x00, 0x01, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x00]);
+        body[5] = 7;
+        let err = from_slice::<Vec<i8>>(&body).unwrap_err();
+        assert!(matches!(err, Error::InvalidListLen), "{err}");
+    }
+
+    #[test]
+    fn unit_encodings() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Marker;
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Holder {
+            nothing: (),
+        }
+
+        let empty = document(&[0x0a, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(to_vec(&()).unwrap(), empty);
+        assert_eq!(to_vec(&Marker).unwrap(), empty);
+        from_slice::<()>(&empty).unwrap();
+        assert_eq!(from_slice::<Marker>(&empty).unwrap(), Marker);
+
+        let absent = document(&[0x00]);
+        from_slice::<()>(&absent).unwrap();
+        assert_eq!(from_slice::<Marker>(&absent).unwrap(), Marker);
+
+        let full = to_vec(&Size { w: 1, h: 2 }).unwrap();
+        for err in [
+            from_slice::<()>(&full).unwrap_err(),
+            from_slice::<Marker>(&full).unwrap_err(),
+        ] {
+            assert!(matches!(err, Error::ExpectedUnit), "{err}");
+        }
+        let nested = to_vec(&BTreeMap::from([("nothing", Size { w: 1, h: 2 })])).unwrap();
+        let err = from_slice::<Holder>(&nested).unwrap_err();
+        assert!(matches!(err, Error::ExpectedUnit), "{err}");
+        let scalar = to_vec(&BTreeMap::from([("nothing", 0i8)])).unwrap();
+        let err = from_slice::<Holder>(&scalar).unwrap_err();
+        assert!(
+            matches!(err, Error::TagMismatch(Tag::Compound, Tag::Byte)),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn utf16_strings() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Legacy {
+            #[serde(with = "crate::codec::utf16")]
+            name: String,
+        }
+
+        for (name, units) in [
+            ("Steve", &b"S\0t\0e\0v\0e\0"[..]),
+            ("\u{1F600}!", b"\x3d\xd8\x00\xde!\0"),
+        ] {
+            let legacy = Legacy {
+                name: name.to_string(),
+            };
+            let bytes = to_vec(&legacy).unwrap();
+            assert_eq!(
+                bytes,
+                to_vec(&BTreeMap::from([("name", Bytes::new(units))])).unwrap()
+            );
+            assert_eq!(from_slice::<Legacy>(&bytes).unwrap(), legacy);
+        }
+
+        for units in [&b"S\0t"[..], b"\x3d\xd8!\0"] {
+            let bytes = to_vec(&BTreeMap::from([("name", Bytes::new(units))])).unwrap();
+            let err = from_slice::<Legacy>(&bytes).unwrap_err();
+            assert!(err.to_string().contains("UTF-16LE"), "{err}");
+        }
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Login {
+            name: String,
+            protocol: i32,
+        }
+
+        let login = Login {
+            name: "Steve".to_string(),
+            protocol: 763,
+        };
+        let mut packet = to_vec(&login).unwrap();
+        packet.extend_from_slice(b"\x00\x2a\xff");
+
+        let mut de = Deserializer::from_slice(&packet).unwrap();
+        assert_eq!(Login::deserialize(&mut de).unwrap(), login);
+        assert_eq!(de.trailing().unwrap(), b"\x00\x2a\xff");
+
+        let mut de = Deserializer::from_reader(&packet[..]).unwrap();
+        assert_eq!(Login::deserialize(&mut de).unwrap(), login);
+        assert_eq!(de.trailing().unwrap(), b"\x00\x2a\xff");
+
+        // A bare `End` root has no terminator, so the next byte already belongs to the caller.
+        let packet = document(&[0x00, 0x00]);
+        let mut de = Deserializer::from_slice(&packet).unwrap();
+        <()>::deserialize(&mut de).unwrap();
+        assert_eq!(de.trailing().unwrap(), b"\x00");
+    }
+
+    #[test]
+    fn flatten_merges_compounds() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Entity {
+            id: String,
+            health: f32,
+            #[serde(flatten)]
+            extra: BTreeMap<String, Value>,
+        }
+
+        let entity = Entity {
+            id: "pig".to_string(),
+            health: 10.0,
+            extra: BTreeMap::from([
+                ("age".to_string(), Value::Int(-24000)),
+                ("saddle".to_string(), Value::Byte(1)),
+            ]),
+        };
+        let bytes = to_vec(&entity).unwrap();
+        assert_eq!(
+            bytes,
+            document(&[
+                0x0a, 0x00, 0x00, // root compound
+                0x08, 0x02, 0x00, b'i', b'd', 0x03, 0x00, b'p', b'i', b'g', // id: "pig"
+                0x05, 0x06, 0x00, b'h', b'e', b'a', b'l', b't', b'h', 0x00, 0x00, 0x20, 0x41, 0x03,
+                0x03, 0x00, b'a', b'g', b'e', 0x40, 0xa2, 0xff, 0xff, // age: -24000
+                0x01, 0x06, 0x00, b's', b'a', b'd', b'd', b'l', b'e', 0x01, // saddle: 1
+                0x00, 0x00,
+            ])
+        );
+        assert_eq!(from_slice::<Entity>(&bytes).unwrap(), entity);
+
+        // Each struct checks its own keys, so the same names may appear in other entries.
+        let herd = BTreeMap::from([("first", &entity), ("second", &entity)]);
+        let bytes = to_vec(&herd).unwrap();
+        assert_eq!(
+            from_slice::<BTreeMap<String, Entity>>(&bytes).unwrap()["second"],
+            entity
+        );
+
+        // A flattened key that repeats a named field would give a document the struct can't
+        // read back, so it's refused.
+        let mut clash = entity;
+        clash.extra.insert("id".to_string(), Value::from("cow"));
+        assert!(matches!(
+            to_vec(&clash),
+            Err(Error::DuplicateKey(key)) if key == "id"
+        ));
+
+        // Maps of known length are trusted to have distinct keys, even when two of them are
+        // written as the same string. Only maps of unknown length are checked.
+        #[derive(PartialEq, Eq, PartialOrd, Ord)]
+        struct Alias(u8);
+        impl Serialize for Alias {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str("x")
+            }
+        }
+        let aliased = BTreeMap::from([(Alias(1), 1), (Alias(2), 2)]);
+        to_vec(&aliased).unwrap();
+        struct Unsized<'a>(&'a BTreeMap<Alias, i32>);
+        impl Serialize for Unsized<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut map = serializer.serialize_map(None)?;
+                for (key, value) in self.0 {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+        assert!(matches!(
+            to_vec(&Unsized(&aliased)),
+            Err(Error::DuplicateKey(key)) if key == "x"
+        ));
+    }
+
+    #[test]
+    fn document_streams() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Record {
+            id: i32,
+            name: String,
+        }
+
+        let records: Vec<Record> = (0..3)
+            .map(|id| Record {
+                id,
+                name: format!("record {id}"),
+            })
+            .collect();
+        let mut batch = to_vec(&records[0]).unwrap();
+        Serializer::new_interned(&mut batch)
+            .and_then(|mut ser| {
+                records[1].serialize(&mut ser)?;
+                ser.finish()
+            })
+            .unwrap();
+        batch.extend(to_vec(&records[2]).unwrap());
+
+        let de = Deserializer::from_reader(&batch[..]).unwrap();
+        let decoded = de.into_iter::<Record>().collect::<crate::Result<Vec<_>>>();
+        assert_eq!(decoded.unwrap(), records);
+
+        let de = Deserializer::from_slice(&batch).unwrap();
+        let odd: Vec<i32> = de
+            .into_iter::<Record>()
+            .map(|record| record.unwrap().id)
+            .filter(|id| id % 2 == 1)
+            .collect();
+        assert_eq!(odd, [1]);
+
+        // A cut-off document is reported once, and nothing is read after it.
+        let cut = &batch[..batch.len() - 3];
+        let mut iter = Deserializer::from_slice(cut).unwrap().into_iter::<Record>();
+        assert_eq!(iter.next().unwrap().unwrap(), records[0]);
+        assert_eq!(iter.next().unwrap().unwrap(), records[1]);
+        assert!(matches!(iter.next(), Some(Err(Error::Eof))));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn option_lists() {
+        let sparse: Vec<Option<i32>> = (0..1024)
+            .map(|i| (i % 16 == 3).then_some(i * 100))
+            .collect();
+        for list in [
+            sparse.clone(),
+            vec![None; 20],
+            (0..20).map(Some).collect(),
+            Vec::new(),
+        ] {
+            let bytes = to_vec(&OptionList(list.clone())).unwrap();
+            assert_eq!(from_slice::<OptionList<i32>>(&bytes).unwrap().0, list);
+        }
+
+        let dense = to_vec(&OptionList(sparse.clone())).unwrap();
+        let mut naive = Vec::new();
+        let mut ser = Serializer::new(&mut naive).unwrap();
+        ser.set_option_repr(OptionRepr::Compound);
+        BTreeMap::from([("list", &sparse)])
+            .serialize(&mut ser)
+            .unwrap();
+        ser.finish().unwrap();
+        assert!(
+            dense.len() * 4 < naive.len(),
+            "{} vs {}",
+            dense.len(),
+            naive.len()
+        );
+
+        // The bitset must account for every value.
+        let bytes = to_vec(&BTreeMap::from([
+            ("present", Value::ByteArray(vec![2, 0, 0, 0, 0b01])),
+            ("values", Value::list(vec![1i32, 2]).unwrap()),
+        ]))
+        .unwrap();
+        assert!(from_slice::<OptionList<i32>>(&bytes).is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn jsonl_transcoding() {
+        use crate::transcode_to_jsonl;
+
+        #[derive(Serialize)]
+        struct Event<'a> {
+            kind: &'a str,
+            at: i64,
+            pos: (f64, f64),
+            tags: Vec<&'a str>,
+            data: &'a Bytes,
+        }
+
+        let mut stream = to_vec(&Event {
+            kind: "join",
+            at: 1_700_000_000_000,
+            pos: (0.5, -2.0),
+            tags: vec!["new"],
+            data: Bytes::new(&[1, 2]),
+        })
+        .unwrap();
+        stream.extend(to_vec(&Value::compound().insert("nan", f32::NAN).build()).unwrap());
+
+        let mut jsonl = Vec::new();
+        assert_eq!(transcode_to_jsonl(&stream[..], &mut jsonl).unwrap(), 2);
+        assert_eq!(
+            String::from_utf8(jsonl).unwrap(),
+            concat!(
+                r#"{"kind":"join","at":1700000000000,"pos":[0.5,-2.0],"tags":["new"],"data":[1,2]}"#,
+                "\n",
+                r#"{"nan":null}"#,
+                "\n",
+            )
+        );
+
+        assert_eq!(transcode_to_jsonl(&[][..], Vec::new()).unwrap(), 0);
+        let cut = &stream[..stream.len() - 2];
+        assert!(transcode_to_jsonl(cut, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn nul_in_field_names() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Framed {
+            #[serde(rename = "a\0")]
+            a: i8,
+            b: i8,
+        }
+
+        // The encoded NUL is `C0 80`, so no byte of the name is `00`.
+        let framed = Framed { a: 1, b: 2 };
+        let bytes = to_vec(&framed).unwrap();
+        assert_eq!(
+            bytes,
+            document(&[
+                0x0a, 0x00, 0x00, // root compound
+                0x01, 0x03, 0x00, b'a', 0xc0, 0x80, 0x01, // "a\0": 1
+                0x01, 0x01, 0x00, b'b', 0x02, // b: 2
+                0x00, 0x00,
+            ])
+        );
+        assert_eq!(from_slice::<Framed>(&bytes).unwrap(), framed);
+        assert_eq!(from_reader::<_, Framed>(&bytes[..]).unwrap(), framed);
+        validate(&bytes).unwrap();
+        assert_eq!(root_keys(&bytes[..]).unwrap(), ["a\0", "b"]);
+
+        // Some writers put a raw `00` in names instead. Names are read by their length prefix
+        // alone, so it stays part of the name and the compound carries on after it.
+        let raw = document(&[
+            0x0a, 0x00, 0x00, // root compound
+            0x01, 0x02, 0x00, b'a', 0x00, 0x01, // "a\0" with a raw NUL: 1
+            0x01, 0x01, 0x00, b'b', 0x02, // b: 2
+            0x00, 0x00,
+        ]);
+        assert_eq!(from_slice::<Framed>(&raw).unwrap(), framed);
+    }
+
+    #[test]
+    fn bare_streams() {
+        let known = Known { a: 1, b: 2 };
+        let mut ser = Serializer::new_bare(Vec::new());
+        known.serialize(&mut ser).unwrap();
+        let bytes = ser.finish().unwrap();
+        let with_header = to_vec(&known).unwrap();
+        assert_eq!(bytes, with_header[7..with_header.len() - 1]);
+        assert_eq!(bytes[0], Tag::Compound as u8);
+
+        let mut de = Deserializer::new_bare(SliceRead::new(&bytes));
+        assert_eq!(Known::deserialize(&mut de).unwrap(), known);
+        Deserializer::new_bare(SliceRead::new(&bytes))
+            .validate()
+            .unwrap();
+        let mut de = Deserializer::new_bare(IoRead::new(&bytes[..]));
+        assert_eq!(Known::deserialize(&mut de).unwrap(), known);
+        assert!(de.trailing().unwrap().is_empty());
+
+        // Without a header, the default constructors refuse the stream.
+        assert!(matches!(
+            from_slice::<Known>(&bytes),
+            Err(Error::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn unit_only_enums() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+        enum Facing {
+            North,
+            East,
+            South,
+            West,
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Block {
+            facing: Facing,
+            sides: Vec<Facing>,
+        }
+
+        let block = Block {
+            facing: Facing::East,
+            sides: vec![Facing::North, Facing::West, Facing::South],
+        };
+        let bytes = to_vec(&block).unwrap();
+        let Value::Compound(fields) = from_slice::<Value>(&bytes).unwrap() else {
+            panic!("expected a compound");
+        };
+        assert_eq!(fields["facing"], Value::from("East"));
+        assert_eq!(from_slice::<Block>(&bytes).unwrap(), block);
+        assert_eq!(from_reader::<_, Block>(&bytes[..]).unwrap(), block);
+
+        let bytes = to_vec(&Facing::West).unwrap();
+        assert_eq!(from_slice::<Facing>(&bytes).unwrap(), Facing::West);
+
+        let bytes = to_vec(&BTreeMap::from([("facing", "Up")])).unwrap();
+        let err = from_slice::<Block>(&bytes).unwrap_err();
+        assert!(err.to_string().contains("unknown variant `Up`"), "{err}");
+
+        // A compound keyed by a unit variant's name is the form of a data variant, which the tag
+        // tells apart from the string.
+        let bytes = to_vec(&BTreeMap::from([("North", 0i8)])).unwrap();
+        assert!(matches!(
+            from_slice::<Facing>(&bytes),
+            Err(Error::TagMismatch(Tag::String, Tag::Compound))
+        ));
+    }
+
+    #[test]
+    fn multi_element_lists() {
+        // The element tag and count are written once, before the first element, and every
+        // element after it is a bare payload.
+        let bytes = to_vec(&vec![1i32, -2, 3]).unwrap();
+        assert_eq!(
+            bytes,
+            document(&[
+                0x09, 0x00, 0x00, // root list
+                0x03, 0x03, 0x00, 0x00, 0x00, // of 3 ints
+                0x01, 0x00, 0x00, 0x00, 0xfe, 0xff, 0xff, 0xff, 0x03, 0x00, 0x00, 0x00, //
+                0x00,
+            ])
+        );
+        assert_eq!(from_slice::<Vec<i32>>(&bytes).unwrap(), [1, -2, 3]);
+
+        let nested = vec![vec!["a", "b"], vec![], vec!["c"]];
+        let bytes = to_vec(&nested).unwrap();
+        assert_eq!(from_slice::<Vec<Vec<String>>>(&bytes).unwrap(), nested);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn chunked_streams() {
+        use std::pin::Pin;
+        use std::task::{Context, Poll, Waker};
+
+        use futures_core::Stream;
+
+        use crate::from_stream;
+
+        /// Hands out one chunk every other poll, to make the decoder wait.
+        struct Chunks {
+            chunks: std::vec::IntoIter<bytes::Bytes>,
+            ready: bool,
+        }
+
+        impl Stream for Chunks {
+            type Item = bytes::Bytes;
+
+            fn poll_next(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+            ) -> Poll<Option<bytes::Bytes>> {
+                self.ready = !self.ready;
+                if self.ready {
+                    Poll::Ready(self.chunks.next())
+                } else {
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            }
+        }
+
+        fn collect<T: DeserializeOwned>(bytes: &[u8]) -> Vec<crate::Result<T>> {
+            let chunks = Chunks {
+                chunks: bytes
+                    .chunks(3)
+                    .map(bytes::Bytes::copy_from_slice)
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+                ready: false,
+            };
+            let mut stream = from_stream::<_, T>(chunks);
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut items = Vec::new();
+            loop {
+                match Pin::new(&mut stream).poll_next(&mut cx) {
+                    Poll::Ready(Some(item)) => items.push(item),
+                    Poll::Ready(None) => return items,
+                    Poll::Pending => {}
+                }
+            }
+        }
+
+        let known = Known { a: 1, b: 2 };
+        let mut bytes = to_vec(&known).unwrap();
+        bytes.extend(to_vec(&Known { a: 3, b: 4 }).unwrap());
+        let decoded = collect::<Known>(&bytes);
+        assert_eq!(
+            decoded
+                .into_iter()
+                .collect::<crate::Result<Vec<_>>>()
+                .unwrap(),
+            [known, Known { a: 3, b: 4 }]
+        );
+
+        assert!(collect::<Known>(&[]).is_empty());
+        let decoded = collect::<Known>(&bytes[..bytes.len() - 1]);
+        assert_eq!(decoded.len(), 2);
+        assert!(matches!(decoded[1], Err(Error::Eof)));
+    }
+
+    #[test]
+    fn value_pointers() {
+        let mut value = Value::compound()
+            .insert(
+                "player",
+                Value::compound()
+                    .insert("pos", Value::list(vec![1.5f64, 64.0, -3.0]).unwrap())
+                    .insert("a/b", 1i8)
+                    .insert("m~n", 2i8)
+                    .insert("7", 3i8)
+                    .build(),
+            )
+            .build();
+
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(value.pointer("/player/pos/1"), Some(&Value::Double(64.0)));
+        assert_eq!(value.pointer("/player/a~1b"), Some(&Value::Byte(1)));
+        assert_eq!(value.pointer("/player/m~0n"), Some(&Value::Byte(2)));
+        assert_eq!(value.pointer("/player/7"), Some(&Value::Byte(3)));
+
+        for missing in [
+            "/player/pos/3",
+            "/player/pos/01",
+            "/player/pos/+1",
+            "/player/pos/x",
+            "/player/name",
+            "/player/a/b",
+            "/player/pos/1/0",
+            "player/pos",
+        ] {
+            assert_eq!(value.pointer(missing), None, "{missing}");
+        }
+
+        *value.pointer_mut("/player/pos/2").unwrap() = Value::Double(8.0);
+        assert_eq!(value.pointer("/player/pos/2"), Some(&Value::Double(8.0)));
+        assert!(value.pointer_mut("/player/pos/9").is_none());
+    }
+
+    #[test]
+    fn identifiers() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(field_identifier, rename_all = "lowercase")]
+        enum Field {
+            Name,
+            Age,
+        }
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(variant_identifier)]
+        enum Kind {
+            Pig,
+            Cow,
+        }
+
+        let bytes = to_vec(&"age").unwrap();
+        assert_eq!(from_slice::<Field>(&bytes).unwrap(), Field::Age);
+        assert_eq!(from_reader::<_, Field>(&bytes[..]).unwrap(), Field::Age);
+        let bytes = to_vec(&BTreeMap::from([("kinds", ["Cow", "Pig"])])).unwrap();
+        assert_eq!(
+            from_slice::<BTreeMap<String, Vec<Kind>>>(&bytes).unwrap()["kinds"],
+            [Kind::Cow, Kind::Pig]
+        );
+
+        let err = from_slice::<Kind>(&to_vec(&"Sheep").unwrap()).unwrap_err();
+        assert!(err.to_string().contains("unknown variant `Sheep`"), "{err}");
+        assert!(matches!(
+            from_slice::<Field>(&to_vec(&1i32).unwrap()),
+            Err(Error::TagMismatch(Tag::String, Tag::Int))
+        ));
     }
 }
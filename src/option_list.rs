@@ -0,0 +1,60 @@
+use serde::de::{self, Deserialize};
+use serde::ser::Serialize;
+
+/// A list of optional values stored densely: a [`bitset`](crate::codec::bitset) recording which
+/// elements are present, followed by a list of only the present ones.
+///
+/// A plain `Vec<Option<T>>` has to give every `None` a place in the list, which costs a whole
+/// element each. Here a `None` costs a single bit, so sparse lists shrink to little more than
+/// their `Some` values. The two parts are stored as the fields `present` and `values` of a
+/// compound. An all-`None` list has an empty `values` list, and an all-`Some` list still pays
+/// for the bitset, about one byte per eight elements.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct OptionList<T>(pub Vec<Option<T>>);
+
+#[derive(serde::Serialize)]
+#[serde(rename = "OptionList")]
+struct Dense<'a, T> {
+    #[serde(with = "crate::codec::bitset")]
+    present: Vec<bool>,
+    values: Vec<&'a T>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename = "OptionList", bound = "T: Deserialize<'de>")]
+struct DecodedDense<T> {
+    #[serde(with = "crate::codec::bitset")]
+    present: Vec<bool>,
+    values: Vec<T>,
+}
+
+impl<T: Serialize> Serialize for OptionList<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Dense {
+            present: self.0.iter().map(Option::is_some).collect(),
+            values: self.0.iter().flatten().collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for OptionList<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let dense = DecodedDense::<T>::deserialize(deserializer)?;
+        let present = dense.present.iter().filter(|&&set| set).count();
+        if present != dense.values.len() {
+            return Err(de::Error::invalid_length(
+                dense.values.len(),
+                &format!("{present} values, one for each bit set").as_str(),
+            ));
+        }
+        let mut values = dense.values.into_iter();
+        Ok(OptionList(
+            dense
+                .present
+                .into_iter()
+                .map(|set| if set { values.next() } else { None })
+                .collect(),
+        ))
+    }
+}
@@ -0,0 +1,155 @@
+use std::ops::Range;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::de::{read_header, Deserializer};
+use crate::error::{Error, Result};
+use crate::{from_slice, swap_endianness, to_vec, Tag, Value};
+
+/// Replaces the value at `path` in the encoded document `buf` without decoding the rest of it.
+///
+/// Each path segment names a field of a compound or, as a decimal index, an element of a list.
+/// When the new value encodes to the same number of bytes as the old one it is overwritten in
+/// place; otherwise the bytes after it are shifted. A compound field may change its tag, but a
+/// list element must keep the tag shared by the list.
+pub fn patch_field(buf: &mut Vec<u8>, path: &[&str], new_value: Value) -> Result<()> {
+    // Replacing a value could drop the first use of a name that later fields refer to.
+    let header = read_header(&buf[..])?;
+    if header.has_interned_names() {
+        Err(Error::InternedPatch)?
+    }
+    // The new value is encoded with `i32` list lengths.
+    if header.has_compact_lists() {
+        Err(Error::CompactPatch)?
+    }
+    let location = Deserializer::locate(buf, path)?;
+    if !location.little_endian {
+        Err(Error::BigEndianPatch)?
+    }
+    let tag = new_value.tag();
+    if location.tag_offset.is_none() && tag != location.tag {
+        Err(Error::TagMismatch(location.tag, tag))?
+    }
+
+    // Encoding the value as a document of its own puts it after the header, the root tag and an
+    // empty name, and before the document terminator. It is encoded before `buf` is touched, so
+    // a value that fails to encode leaves the document as it was.
+    let encoded = to_vec(&new_value)?;
+    let payload = &encoded[10..encoded.len() - 1];
+    if let Some(offset) = location.tag_offset {
+        buf[offset] = tag as u8;
+    }
+    if payload.len() == location.payload.len() {
+        buf[location.payload].copy_from_slice(payload);
+    } else {
+        buf.splice(location.payload, payload.iter().copied());
+    }
+    Ok(())
+}
+
+/// Overwrites the fields of `target` that the compound in `patch` has, leaving the rest as they
+/// are.
+///
+/// `target` is encoded, the patch's fields are spliced into the encoding without being decoded,
+/// and the result is decoded back into `target`. Nested compounds are merged field by field,
+/// while lists, arrays and scalars are replaced whole, and every tag is kept as it was encoded.
+/// A field can only be added or changed, never removed, so a `None` can't be written over a
+/// `Some`. A `target` that isn't encoded as a compound is replaced by the patch whole.
+///
+/// The patch may be big-endian, but a patch with interned names or compact list lengths fails
+/// with [`Error::InternedPatch`] or [`Error::CompactPatch`]. A patch field whose tag doesn't
+/// suit the type of the field it replaces fails the final decode, typically with
+/// [`Error::TagMismatch`], and `target` is then left unchanged.
+pub fn merge_from_slice<T: Serialize + DeserializeOwned>(
+    target: &mut T,
+    patch: &[u8],
+) -> Result<()> {
+    let header = read_header(patch)?;
+    if header.has_interned_names() {
+        Err(Error::InternedPatch)?
+    }
+    if header.has_compact_lists() {
+        Err(Error::CompactPatch)?
+    }
+    let swapped;
+    let patch = if header.is_big_endian() {
+        let mut copy = patch.to_vec();
+        swap_endianness(&mut copy)?;
+        swapped = copy;
+        &swapped[..]
+    } else {
+        patch
+    };
+    let (tag, patch_root) = root(patch)?;
+    if tag != Tag::Compound {
+        Err(Error::TagMismatch(Tag::Compound, tag))?
+    }
+
+    let encoded = to_vec(target)?;
+    let (tag, target_root) = root(&encoded)?;
+    if tag != Tag::Compound {
+        *target = from_slice(patch)?;
+        return Ok(());
+    }
+    let mut merged = encoded[..target_root.start].to_vec();
+    merge_compound(
+        &encoded,
+        target_root.start,
+        patch,
+        patch_root.start,
+        &mut merged,
+    )?;
+    merged.extend_from_slice(&encoded[target_root.end..]);
+    *target = from_slice(&merged)?;
+    Ok(())
+}
+
+/// Finds the tag and payload of the root of a plain document. An `End` root has no name, so
+/// it is told apart before the root is walked.
+fn root(doc: &[u8]) -> Result<(Tag, Range<usize>)> {
+    if doc.get(7) == Some(&(Tag::End as u8)) {
+        return Ok((Tag::End, 8..8));
+    }
+    let root = Deserializer::locate(doc, &[])?;
+    Ok((root.tag, root.payload))
+}
+
+/// Writes the fields of the target compound whose payload starts at `target_start`, each
+/// replaced by the patch field of the same name if the patch compound at `patch_start` has one,
+/// followed by the patch's other fields and the terminator.
+fn merge_compound(
+    target: &[u8],
+    target_start: usize,
+    patch: &[u8],
+    patch_start: usize,
+    out: &mut Vec<u8>,
+) -> Result<()> {
+    let mut updates = Deserializer::fields(patch, patch_start)?;
+    for field in Deserializer::fields(target, target_start)? {
+        let update = match updates.iter().position(|update| update.name == field.name) {
+            Some(index) => updates.remove(index),
+            None => {
+                out.extend_from_slice(&target[field.header.start..field.payload.end]);
+                continue;
+            }
+        };
+        out.extend_from_slice(&patch[update.header]);
+        if field.tag == Tag::Compound && update.tag == Tag::Compound {
+            merge_compound(
+                target,
+                field.payload.start,
+                patch,
+                update.payload.start,
+                out,
+            )?;
+        } else {
+            out.extend_from_slice(&patch[update.payload]);
+        }
+    }
+    for update in updates {
+        out.extend_from_slice(&patch[update.header.start..update.payload.end]);
+    }
+    out.push(Tag::End as u8);
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::borrow::Cow;
+use std::io::{self, Read as _};
+
+use crate::error::{Error, Result};
+
+/// A source of ShadeNBT bytes for the [`Deserializer`](crate::Deserializer).
+///
+/// Sources that hold the whole document in memory can hand out borrowed data, which lets
+/// zero-copy types like `&'de [u8]` be deserialized.
+pub trait Read<'de> {
+    /// Fills `buf` entirely, failing with [`Error::Eof`] if the input ends first.
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()>;
+
+    /// Reads the next `len` bytes, borrowing them from the input if possible.
+    fn read_bytes(&mut self, len: usize) -> Result<Cow<'de, [u8]>>;
+
+    /// Discards the next `len` bytes.
+    fn skip(&mut self, len: usize) -> Result<()>;
+}
+
+/// Reads from any [`io::Read`], copying everything it returns.
+pub struct IoRead<R> {
+    reader: R,
+    consumed: usize,
+}
+
+impl<R: io::Read> IoRead<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            consumed: 0,
+        }
+    }
+
+    /// The number of bytes taken from the reader so far. Nothing is read ahead, so this is
+    /// exactly the amount decoded.
+    pub(crate) fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<'de, R: io::Read> Read<'de> for IoRead<R> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.reader.read_exact(buf).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => Error::Eof,
+            _ => Error::Io(e),
+        })?;
+        self.consumed += buf.len();
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Cow<'de, [u8]>> {
+        // Don't trust the length enough to allocate it up front.
+        let mut buf = Vec::new();
+        (&mut self.reader).take(len as u64).read_to_end(&mut buf)?;
+        if buf.len() != len {
+            Err(Error::Eof)?
+        }
+        self.consumed += len;
+        Ok(Cow::Owned(buf))
+    }
+
+    fn skip(&mut self, len: usize) -> Result<()> {
+        let skipped = io::copy(&mut (&mut self.reader).take(len as u64), &mut io::sink())?;
+        if skipped != len as u64 {
+            Err(Error::Eof)?
+        }
+        self.consumed += len;
+        Ok(())
+    }
+}
+
+/// Reads from a byte slice, borrowing from it wherever the target type allows.
+pub struct SliceRead<'a> {
+    slice: &'a [u8],
+}
+
+impl<'a> SliceRead<'a> {
+    pub fn new(slice: &'a [u8]) -> Self {
+        Self { slice }
+    }
+
+    /// The number of bytes not yet read.
+    pub(crate) fn remaining(&self) -> usize {
+        self.slice.len()
+    }
+
+    /// The bytes not yet read.
+    pub(crate) fn into_rest(self) -> &'a [u8] {
+        self.slice
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.slice.len() {
+            Err(Error::Eof)?
+        }
+        let (taken, rest) = self.slice.split_at(len);
+        self.slice = rest;
+        Ok(taken)
+    }
+}
+
+impl<'a> Read<'a> for SliceRead<'a> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        buf.copy_from_slice(self.take(buf.len())?);
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Cow<'a, [u8]>> {
+        self.take(len).map(Cow::Borrowed)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<()> {
+        self.take(len).map(drop)
+    }
+}
@@ -0,0 +1,41 @@
+/// How enum variants are laid out by the [`Serializer`](crate::Serializer) and expected by the
+/// [`Deserializer`](crate::Deserializer).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EnumRepr {
+    /// Unit variants are written as a string holding the variant name, and every other variant as
+    /// a compound with a single field named after the variant holding its payload.
+    #[default]
+    External,
+    /// Variants are written as a single compound whose first field, under the given key, holds
+    /// the variant name as a string, followed by the variant's own fields.
+    ///
+    /// Only unit variants, struct variants and newtype variants wrapping a struct or map can be
+    /// represented this way; anything else fails with [`Error::UntaggableVariant`](crate::Error).
+    Internal(&'static str),
+}
+
+/// How `Option` values are laid out by the [`Serializer`](crate::Serializer) and expected by
+/// the [`Deserializer`](crate::Deserializer).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OptionRepr {
+    /// `Some` is written as the bare value and `None` by leaving the field out of its compound,
+    /// which fails for list elements.
+    #[default]
+    Omit,
+    /// Every option is written as a compound, which is empty for `None` and holds the value in a
+    /// field named `"Some"` otherwise. Fields are then always present, `None` can be a list
+    /// element, and `Some(None)` stays apart from `None`.
+    Compound,
+}
+
+/// How tuples, including fixed-size arrays, are laid out by the [`Serializer`](crate::Serializer)
+/// and expected by the [`Deserializer`](crate::Deserializer).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TupleRepr {
+    /// Tuples are written as lists, so every element must have the same tag.
+    #[default]
+    List,
+    /// Tuples are written as a compound whose fields are named after the element indices,
+    /// starting from `"0"`, letting the elements have different tags.
+    Compound,
+}
@@ -1,67 +1,671 @@
-use std::io::Write;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write as _};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::mem;
 
-use serde::{ser, Serialize};
+use serde::ser::{self, Impossible, Serialize};
 
+use crate::array;
+use crate::de::{COMPACT_LISTS_VERSION, INTERNED_VERSION, NAME_REF};
 use crate::error::{Error, Result};
+use crate::{EnumRepr, OptionRepr, Tag, TupleRepr};
 
 pub fn to_vec<T: ?Sized + Serialize>(value: &T) -> Result<Vec<u8>> {
     let mut serializer = Serializer::new(Vec::new())?;
     value.serialize(&mut serializer)?;
-    serializer.output.push(0);
-    Ok(serializer.output)
+    serializer.finish()
 }
 
+/// Serializes `value` into `writer`.
+///
+/// The writer is flushed before returning, so on success every byte of the document has been
+/// passed through any buffering such as a [`BufWriter`](std::io::BufWriter).
 pub fn to_writer<W: Write, T: ?Sized + Serialize>(writer: W, value: &T) -> Result<()> {
     let mut serializer = Serializer::new(writer)?;
     value.serialize(&mut serializer)?;
-    serializer.output.write_all(&[0])?;
+    serializer.finish()?.flush()?;
     Ok(())
 }
 
+/// Serializes `value` into `buf`, returning the number of bytes written.
+///
+/// Fails with [`Error::BufferFull`] if the document doesn't fit. The contents of `buf` are only
+/// meaningful when this returns `Ok`; on error, a partial document may have been written.
+pub fn to_buf<T: ?Sized + Serialize>(buf: &mut [u8], value: &T) -> Result<usize> {
+    let capacity = buf.len();
+    let result = Serializer::new(buf).and_then(|mut serializer| {
+        value.serialize(&mut serializer)?;
+        serializer.finish()
+    });
+    match result {
+        Ok(rest) => Ok(capacity - rest.len()),
+        Err(Error::Io(e)) if e.kind() == io::ErrorKind::WriteZero => Err(Error::BufferFull),
+        Err(e) => Err(e),
+    }
+}
+
+/// The size and tag usage of a serialized value, as measured by [`dry_run`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SerializeStats {
+    /// The length of the whole document, including the header and terminator.
+    pub total_bytes: usize,
+    /// How many values were written with each tag, indexed by the tag byte. Every element of a
+    /// list or array counts, while compound terminators don't.
+    pub tag_counts: [usize; 16],
+}
+
+/// Measures `value` by serializing it without keeping the output.
+pub fn dry_run<T: ?Sized + Serialize>(value: &T) -> Result<SerializeStats> {
+    measure(value, EnumRepr::default())
+}
+
+/// Like [`dry_run`], with enums written under `enum_repr`.
+pub(crate) fn measure<T: ?Sized + Serialize>(
+    value: &T,
+    enum_repr: EnumRepr,
+) -> Result<SerializeStats> {
+    let mut serializer = Serializer::new(Counter(0))?;
+    serializer.set_enum_repr(enum_repr);
+    serializer.tag_counts = Some([0; 16]);
+    value.serialize(&mut serializer)?;
+    let tag_counts = serializer.tag_counts.expect("counting was enabled above");
+    let Counter(total_bytes) = serializer.finish()?;
+    Ok(SerializeStats {
+        total_bytes,
+        tag_counts,
+    })
+}
+
+/// Appends the MUTF-8 encoding of `s` to `out`: nulls become the overlong sequence `C0 80` and
+/// characters outside the BMP become a surrogate pair of three byte sequences.
+fn encode_mutf8(s: &str, out: &mut Vec<u8>) {
+    let bytes = s.as_bytes();
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if c != '\0' && c.len_utf8() < 4 {
+            continue;
+        }
+        out.extend_from_slice(&bytes[start..i]);
+        start = i + c.len_utf8();
+        if c == '\0' {
+            out.extend_from_slice(&[0xc0, 0x80]);
+        } else {
+            let mut units = [0; 2];
+            for unit in c.encode_utf16(&mut units) {
+                let unit = *unit as u32;
+                out.extend_from_slice(&[
+                    0xe0 | (unit >> 12) as u8,
+                    0x80 | (unit >> 6 & 0x3f) as u8,
+                    0x80 | (unit & 0x3f) as u8,
+                ]);
+            }
+        }
+    }
+    out.extend_from_slice(&bytes[start..]);
+}
+
+/// The serializer's writer, with a count of the bytes it has been given.
+struct Output<W> {
+    writer: W,
+    written: usize,
+    /// Set by [`Serializer::set_max_output`].
+    limit: usize,
+    /// The elements of lists of unknown length, innermost last, held back until their count can
+    /// be written in front of them. Held bytes already count towards `written`.
+    held: Vec<Vec<u8>>,
+    /// Whether list lengths are written in LEB128 instead of as an `i32`.
+    compact_lists: bool,
+}
+
+impl<W: Write> Output<W> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        let written = self.written.saturating_add(buf.len());
+        if written > self.limit {
+            Err(Error::LimitExceeded(written, self.limit))?
+        }
+        self.write_uncounted(buf)?;
+        self.written = written;
+        Ok(())
+    }
+
+    fn write_list_len(&mut self, len: i32) -> Result<()> {
+        let (bytes, used) = encode_list_len(len, self.compact_lists);
+        self.write_all(&bytes[..used])
+    }
+
+    fn write_uncounted(&mut self, buf: &[u8]) -> Result<()> {
+        match self.held.last_mut() {
+            Some(held) => held.extend_from_slice(buf),
+            None => self.writer.write_all(buf)?,
+        }
+        Ok(())
+    }
+
+    /// Starts holding back everything written until the matching [`release`](Self::release).
+    fn hold(&mut self) {
+        self.held.push(Vec::new());
+    }
+
+    /// Returns the bytes held since the last [`hold`](Self::hold) without writing them.
+    fn take_held(&mut self) -> Vec<u8> {
+        self.held.pop().expect("taken without holding")
+    }
+
+    /// Writes `header` followed by the bytes held since the last [`hold`](Self::hold).
+    fn release(&mut self, header: &[u8]) -> Result<()> {
+        let held = self.held.pop().expect("released without holding");
+        self.write_all(header)?;
+        self.write_uncounted(&held)
+    }
+}
+
+/// Encodes a list's element count, returning the buffer and how much of it is used. Compact
+/// lengths are LEB128: seven bits per byte, least significant first, with the top bit set on
+/// every byte but the last.
+fn encode_list_len(len: i32, compact: bool) -> ([u8; 5], usize) {
+    let mut bytes = [0; 5];
+    if !compact {
+        bytes[..4].copy_from_slice(&len.to_le_bytes());
+        return (bytes, 4);
+    }
+    let mut rest = len as u32;
+    let mut used = 0;
+    loop {
+        bytes[used] = (rest & 0x7f) as u8;
+        rest >>= 7;
+        used += 1;
+        if rest == 0 {
+            break (bytes, used);
+        }
+        bytes[used - 1] |= 0x80;
+    }
+}
+
+/// A writer that discards everything, only counting the bytes.
+struct Counter(usize);
+
+impl Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns a writer that passes everything written to it on to both `first` and `second`, for
+/// capturing a copy of a document as it is serialized.
+///
+/// Each write goes to `first`, then whatever it accepted goes in full to `second`. The first
+/// error from either aborts the write, so after a failure `first` may hold bytes that `second`
+/// never received.
+pub fn tee<A: Write, B: Write>(first: A, second: B) -> Tee<A, B> {
+    Tee { first, second }
+}
+
+/// The writer returned by [`tee`].
+#[derive(Debug)]
+pub struct Tee<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> Tee<A, B> {
+    /// Returns both writers.
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A: Write, B: Write> Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.first.write(buf)?;
+        self.second.write_all(&buf[..len])?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.first.flush()?;
+        self.second.flush()
+    }
+}
+
 enum FieldInfo {
     None,
-    Named(&'static str),
-    InSeq(Option<i32>),
+    Named(Cow<'static, str>),
+    /// The next value is an element of the innermost list being written.
+    InSeq,
+    /// The next value is the payload of an internally tagged newtype variant, which must continue
+    /// the compound that already holds the tag.
+    Inline(&'static str),
+}
+
+/// A list whose elements are being written.
+struct ListState {
+    header: ListHeader,
+    /// The tag shared by every element, known once the first element is written or fixed up
+    /// front for int and long arrays.
+    tag: Option<Tag>,
+    /// The number of elements written so far.
+    count: usize,
+}
+
+/// When the element tag and count in front of a list's elements get written.
+enum ListHeader {
+    /// Once the first element's tag is known, or when the list ends if it is empty. Holds the
+    /// declared count.
+    Pending(i32),
+    /// Before any element, for an int or long array of the declared count, since its header has
+    /// no element tag.
+    Written(i32),
+    /// When the list ends, in front of the elements held back until then. Set for lists of
+    /// unknown length; `array` leaves the element tag out.
+    Held { array: bool },
+    /// By whoever started the list, once its elements are done.
+    Deferred,
 }
 
 impl FieldInfo {
-    fn write(&mut self, tag: u8, mut w: impl Write) -> Result<()> {
-        let result = match self {
+    /// Writes whatever must precede a value with `tag` and resets the field info. An owned name
+    /// is handed back so that its allocation can be reused for the next map key.
+    fn write(
+        &mut self,
+        tag: Tag,
+        list: Option<&mut ListState>,
+        names: Option<&mut HashMap<String, u16>>,
+        w: &mut Output<impl Write>,
+    ) -> Result<Option<String>> {
+        match mem::replace(self, FieldInfo::None) {
             Self::None => Err(Error::FieldInfoUnset),
-            Self::InSeq(size) => {
-                if let Some(x) = size {
-                    w.write_all(&[tag])?;
-                    w.write_all(&x.to_le_bytes())?;
-                    *size = None;
+            Self::InSeq => {
+                let list = list.expect("list element written outside of a list");
+                list.count += 1;
+                match list.tag {
+                    Some(expected) if expected != tag => Err(Error::TagMismatch(expected, tag)),
+                    Some(_) => Ok(None),
+                    None => {
+                        list.tag = Some(tag);
+                        if let ListHeader::Pending(len) = list.header {
+                            w.write_all(&[tag as u8])?;
+                            w.write_list_len(len)?;
+                        }
+                        Ok(None)
+                    }
                 }
-                Ok(())
             }
             Self::Named(name) => {
-                w.write_all(&[tag])?;
-                let len = u16::try_from(name.len()).map_err(|_| Error::StrLen(name.len()))?;
-                w.write_all(&len.to_le_bytes())?;
-                let mutf8 = mutf8::utf8_to_mutf8(name.as_bytes())?;
-                w.write_all(&mutf8)?;
-                Ok(())
+                w.write_all(&[tag as u8])?;
+                match names.as_ref().and_then(|names| names.get(&*name)) {
+                    Some(index) => w.write_all(&(NAME_REF | index).to_le_bytes())?,
+                    None => write_name(&name, names, w)?,
+                }
+                Ok(match name {
+                    Cow::Owned(name) => Some(name),
+                    Cow::Borrowed(_) => None,
+                })
             }
-        };
-        *self = FieldInfo::None;
-        result
+            Self::Inline(variant) => match tag {
+                Tag::Compound => Ok(None),
+                _ => Err(Error::UntaggableVariant(variant.to_string())),
+            },
+        }
+    }
+}
+
+/// Writes `name` in full, giving it the next index if names are interned.
+fn write_name(
+    name: &str,
+    names: Option<&mut HashMap<String, u16>>,
+    w: &mut Output<impl Write>,
+) -> Result<()> {
+    // Nulls and characters outside the BMP take more bytes in MUTF-8, so the length is that of
+    // the encoded name.
+    let mutf8 = mutf8::utf8_to_mutf8(name.as_bytes())?;
+    let len = u16::try_from(mutf8.len()).map_err(|_| Error::StrLen(mutf8.len()))?;
+    if let Some(names) = names {
+        // A length with the top bit set would read as a reference.
+        if len & NAME_REF != 0 {
+            Err(Error::LimitExceeded(mutf8.len(), usize::from(!NAME_REF)))?
+        }
+        if names.len() < usize::from(NAME_REF) {
+            names.insert(name.to_string(), names.len() as u16);
+        }
     }
+    w.write_all(&len.to_le_bytes())?;
+    w.write_all(&mutf8)
 }
 
 pub struct Serializer<W: Write> {
-    output: W,
+    output: Output<W>,
     field_info: FieldInfo,
+    /// Number of compounds that have been started but not yet terminated.
+    depth: usize,
+    lists: Vec<ListState>,
+    /// Set by an array newtype to the array and element tag the sequence inside it is written
+    /// with.
+    array: Option<(Tag, Tag)>,
+    /// The index of the next element of each tuple being written as a compound.
+    tuples: Vec<usize>,
+    enum_repr: EnumRepr,
+    tuple_repr: TupleRepr,
+    option_repr: OptionRepr,
+    human_readable: bool,
+    canonical_zeros: bool,
+    /// Set by [`Serializer::set_sort_map_keys`].
+    sort_map_keys: bool,
+    /// The encoded key and held entry of each field of the maps being sorted, innermost last.
+    sorted_maps: Vec<Vec<(Vec<u8>, Vec<u8>)>>,
+    /// The keys written so far in each map being written, innermost last, kept only for maps of
+    /// unknown length. Those include structs with flattened fields, whose keys may collide.
+    map_keys: Vec<Option<HashSet<String>>>,
+    /// Emptied key sets of finished maps, kept for the next ones.
+    spare_key_sets: Vec<HashSet<String>>,
+    /// The index of each name written so far, if names are interned.
+    names: Option<HashMap<String, u16>>,
+    /// The allocation of the last map key written, kept for the next one.
+    spare_name: String,
+    /// Holds an encoded string on its way to the output. It never holds anything across the
+    /// serialization of a nested value, so every string can reuse it.
+    scratch: Vec<u8>,
+    /// Per-tag value counts, kept only by [`dry_run`].
+    tag_counts: Option<[usize; 16]>,
+    /// Set by [`Serializer::new_bare`], which leaves out the terminator as well as the header.
+    bare: bool,
 }
 
 impl<W: Write> Serializer<W> {
-    pub fn new(mut output: W) -> Result<Self> {
-        output.write_all(&[0xad, 0x4e, 0x42, 0x54, 0x00, 0x05, 0x80])?;
-        Ok(Self {
-            output,
-            field_info: FieldInfo::Named(""),
-        })
+    pub fn new(output: W) -> Result<Self> {
+        let mut serializer = Self::headless(output);
+        // Numbers are always written little-endian, so the big-endian flag stays clear.
+        serializer
+            .output
+            .write_all(&[0xad, 0x4e, 0x42, 0x54, 0x00, 0x05, 0x00])?;
+        Ok(serializer)
+    }
+
+    /// Creates a serializer that writes each field name in full only the first time it is used,
+    /// and as a two byte reference to that first use afterwards. This saves space in documents
+    /// repeating the same names, such as long lists of similar compounds.
+    ///
+    /// The header carries version 6 instead of 5, so readers that don't know about interning
+    /// reject the document instead of misreading it. The [`Deserializer`](crate::Deserializer)
+    /// recognizes it on its own. Names must be shorter than 32768 bytes, and only the first
+    /// 32768 distinct names are interned; later ones are always written in full.
+    pub fn new_interned(output: W) -> Result<Self> {
+        let mut serializer = Self::headless(output);
+        serializer.names = Some(HashMap::new());
+        serializer
+            .output
+            .write_all(&[0xad, 0x4e, 0x42, 0x54, 0x00, INTERNED_VERSION, 0x00])?;
+        Ok(serializer)
+    }
+
+    /// Creates a serializer that writes the element count of every list in LEB128, taking a
+    /// single byte for lists of up to 127 elements instead of four. Int, long and byte arrays
+    /// keep their `i32` lengths.
+    ///
+    /// The header carries version 7, so readers that don't know about compact lengths reject
+    /// the document instead of misreading it. The [`Deserializer`](crate::Deserializer)
+    /// recognizes it on its own. This can't be combined with interned names.
+    pub fn new_compact_lists(output: W) -> Result<Self> {
+        let mut serializer = Self::headless(output);
+        serializer.output.compact_lists = true;
+        serializer.output.write_all(&[
+            0xad,
+            0x4e,
+            0x42,
+            0x54,
+            0x00,
+            COMPACT_LISTS_VERSION,
+            0x00,
+        ])?;
+        Ok(serializer)
+    }
+
+    /// Creates a serializer for bare tag streams as read by older NBT tools: no header, and no
+    /// terminator after the root, so the output starts at the root tag and ends with its
+    /// payload. Numbers are little-endian, as they are in a default header.
+    ///
+    /// Nothing in the output identifies it as ShadeNBT, so it can only be read back with
+    /// [`Deserializer::new_bare`](crate::Deserializer::new_bare).
+    pub fn new_bare(output: W) -> Self {
+        let mut serializer = Self::headless(output);
+        serializer.bare = true;
+        serializer
+    }
+
+    fn headless(writer: W) -> Self {
+        Self {
+            output: Output {
+                writer,
+                written: 0,
+                limit: usize::MAX,
+                held: Vec::new(),
+                compact_lists: false,
+            },
+            field_info: FieldInfo::Named("".into()),
+            depth: 0,
+            lists: Vec::new(),
+            array: None,
+            tuples: Vec::new(),
+            enum_repr: EnumRepr::default(),
+            tuple_repr: TupleRepr::default(),
+            option_repr: OptionRepr::default(),
+            human_readable: false,
+            canonical_zeros: false,
+            sort_map_keys: false,
+            sorted_maps: Vec::new(),
+            map_keys: Vec::new(),
+            spare_key_sets: Vec::new(),
+            names: None,
+            spare_name: String::new(),
+            scratch: Vec::new(),
+            tag_counts: None,
+            bare: false,
+        }
+    }
+
+    pub fn set_enum_repr(&mut self, repr: EnumRepr) {
+        self.enum_repr = repr;
+    }
+
+    pub fn set_tuple_repr(&mut self, repr: TupleRepr) {
+        self.tuple_repr = repr;
+    }
+
+    pub fn set_option_repr(&mut self, repr: OptionRepr) {
+        self.option_repr = repr;
+    }
+
+    /// Sets what [`is_human_readable`](ser::Serializer::is_human_readable) reports to the types
+    /// being serialized, and to every value nested inside them.
+    ///
+    /// The default of `false` is recommended: types then pick their compact binary forms, which
+    /// suit NBT. Whichever mode is used, the [`Deserializer`](crate::Deserializer) must be set
+    /// to the same one to read the values back.
+    pub fn set_human_readable(&mut self, human_readable: bool) {
+        self.human_readable = human_readable;
+    }
+
+    /// Writes `-0.0` and subnormal floats as `+0.0`, so that values which compare equal, or
+    /// nearly so, encode to the same bytes. Off by default, in which case every float is written
+    /// bit for bit. Decoding needs no matching setting.
+    pub fn set_canonical_zeros(&mut self, canonical: bool) {
+        self.canonical_zeros = canonical;
+    }
+
+    /// Writes the fields of every map in order of their names' encoded bytes rather than in
+    /// iteration order, so that a `HashMap` always encodes to the same bytes. Structs keep their
+    /// declaration order, except those with `#[serde(flatten)]` fields: serde writes them as
+    /// maps, so all of their fields are sorted, flattened or not.
+    ///
+    /// Each field of a map is held in memory until the map ends, so the whole of any map is
+    /// buffered and then copied once more into the output. Not supported with interned names,
+    /// since reordering would move references in front of the names they refer to; maps, and
+    /// so structs with flattened fields, then fail with [`Error::InternedSort`].
+    pub fn set_sort_map_keys(&mut self, sort: bool) {
+        self.sort_map_keys = sort;
+    }
+
+    /// Limits the whole document, header included, to `limit` bytes. A write that would pass
+    /// the limit fails with [`Error::LimitExceeded`] before reaching the writer.
+    ///
+    /// The limit can be hit partway through a value, so whatever the writer has received by
+    /// then is not a valid document and should be discarded.
+    pub fn set_max_output(&mut self, limit: usize) {
+        self.output.limit = limit;
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.output.writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the end of the document and returns the underlying writer, without flushing it.
+    pub fn finish(mut self) -> Result<W> {
+        debug_assert_eq!(self.depth, 0, "unbalanced compound terminators");
+        if !self.bare {
+            self.output.write_all(&[0])?;
+        }
+        Ok(self.output.writer)
+    }
+
+    /// Writes a list named `name` holding the items of `iter`, without collecting them first.
+    ///
+    /// The element count precedes the elements on the wire, so the encoded elements are buffered
+    /// in memory until the iterator is exhausted. When the writer is seekable,
+    /// [`serialize_list_from_iter_seekable`](Self::serialize_list_from_iter_seekable) avoids
+    /// that buffer.
+    pub fn serialize_list_from_iter<I>(&mut self, name: &'static str, iter: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: Serialize,
+    {
+        self.field_info = FieldInfo::Named(name.into());
+        self.serialize_buffered_list(iter)
+    }
+
+    /// Writes a list of unknown length, holding its elements in memory until they are counted.
+    fn serialize_buffered_list<I>(&mut self, iter: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: Serialize,
+    {
+        let mut seq = ser::Serializer::serialize_seq(&mut *self, None)?;
+        for item in iter {
+            ser::SerializeSeq::serialize_element(&mut seq, &item)?;
+        }
+        ser::SerializeSeq::end(seq)
+    }
+
+    /// Writes a value named `name` whose payload has already been encoded, copying `body` to the
+    /// output verbatim.
+    ///
+    /// `body` must be exactly one well-formed little-endian payload for `tag`, without the tag or
+    /// name, such as a compound's fields up to and including its terminator. Nothing is
+    /// validated, so a malformed body silently corrupts the rest of the document. The value is
+    /// written as a named field, so this is only meaningful at the root or inside a map or
+    /// struct, not as a list element.
+    pub fn serialize_raw(&mut self, name: &'static str, tag: Tag, body: &[u8]) -> Result<()> {
+        self.field_info = FieldInfo::Named(name.into());
+        self.write_header(tag)?;
+        self.output.write_all(body)?;
+        Ok(())
+    }
+
+    /// Writes the elements of `iter` as the body of a list whose header has been reserved by the
+    /// caller, returning the element tag and count to fill into it.
+    fn serialize_elements<I>(&mut self, iter: I) -> Result<(Tag, i32)>
+    where
+        I: IntoIterator,
+        I::Item: Serialize,
+    {
+        self.lists.push(ListState {
+            header: ListHeader::Deferred,
+            tag: None,
+            count: 0,
+        });
+        for item in iter {
+            self.field_info = FieldInfo::InSeq;
+            item.serialize(&mut *self)?;
+        }
+        let list = self.lists.pop().expect("list state was pushed above");
+        let len = i32::try_from(list.count).map_err(|_| Error::SeqLen(list.count))?;
+        Ok((list.tag.unwrap_or(Tag::End), len))
+    }
+
+    fn write_header(&mut self, tag: Tag) -> Result<()> {
+        // An inlined payload continues the variant's compound, so it isn't a value of its own.
+        if let (Some(counts), false) = (
+            &mut self.tag_counts,
+            matches!(self.field_info, FieldInfo::Inline(_)),
+        ) {
+            counts[tag as usize] += 1;
+        }
+        let spare = self.field_info.write(
+            tag,
+            self.lists.last_mut(),
+            self.names.as_mut(),
+            &mut self.output,
+        )?;
+        if let Some(name) = spare {
+            self.spare_name = name;
+        }
+        Ok(())
+    }
+
+    /// Opens the compound an enum variant is written into. For externally tagged enums the
+    /// payload is then named after the variant, otherwise the variant name is written under the
+    /// tag key.
+    fn begin_variant(&mut self, variant: &'static str) -> Result<()> {
+        self.write_header(Tag::Compound)?;
+        match self.enum_repr {
+            EnumRepr::External => self.field_info = FieldInfo::Named(variant.into()),
+            EnumRepr::Internal(key) => {
+                self.field_info = FieldInfo::Named(key.into());
+                ser::Serializer::serialize_str(&mut *self, variant)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Serializer<W> {
+    /// Writes a list named `name` holding the items of `iter`, streaming the elements straight to
+    /// the writer and seeking back to fill in the element tag and count afterwards.
+    pub fn serialize_list_from_iter_seekable<I>(
+        &mut self,
+        name: &'static str,
+        iter: I,
+    ) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: Serialize,
+    {
+        self.field_info = FieldInfo::Named(name.into());
+        self.write_header(Tag::List)?;
+        let header = self.output.writer.stream_position()?;
+        // A compact length is reserved at its full five bytes and padded out to them.
+        let compact = self.output.compact_lists;
+        let reserved = if compact { 6 } else { 5 };
+        self.output.write_all(&[0; 6][..reserved])?;
+        let (tag, len) = self.serialize_elements(iter)?;
+        let (mut bytes, used) = encode_list_len(len, compact);
+        if compact {
+            bytes[used - 1..4].iter_mut().for_each(|b| *b |= 0x80);
+        }
+        // The reserved bytes are overwritten in place, so they aren't counted again.
+        let writer = &mut self.output.writer;
+        let end = writer.stream_position()?;
+        writer.seek(SeekFrom::Start(header))?;
+        writer.write_all(&[tag as u8])?;
+        writer.write_all(&bytes[..reserved - 1])?;
+        writer.seek(SeekFrom::Start(end))?;
+        Ok(())
     }
 }
 
@@ -78,15 +682,17 @@ impl<W: Write> ser::Serializer for &mut Serializer<W> {
     type SerializeTupleVariant = Self;
 
     fn is_human_readable(&self) -> bool {
-        false
+        self.human_readable
     }
 
     fn serialize_bool(self, v: bool) -> Result<()> {
         self.serialize_u8(v.into())
     }
 
+    /// There is no char tag, so a `char` is written as a one character string and can't be told
+    /// apart from one on the wire. Decoding into a [`Value`](crate::Value) gives a string.
     fn serialize_char(self, v: char) -> Result<()> {
-        self.serialize_str(&v.to_string())
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
     }
 
     fn serialize_i8(self, v: i8) -> Result<()> {
@@ -110,148 +716,347 @@ impl<W: Write> ser::Serializer for &mut Serializer<W> {
     }
 
     fn serialize_u8(self, v: u8) -> Result<()> {
-        self.field_info.write(0x01, &mut self.output)?;
+        self.write_header(Tag::Byte)?;
         self.output.write_all(&[v])?;
         Ok(())
     }
 
     fn serialize_u16(self, v: u16) -> Result<()> {
-        self.field_info.write(0x02, &mut self.output)?;
+        self.write_header(Tag::Short)?;
         self.output.write_all(&v.to_le_bytes())?;
         Ok(())
     }
 
     fn serialize_u32(self, v: u32) -> Result<()> {
-        self.field_info.write(0x03, &mut self.output)?;
+        self.write_header(Tag::Int)?;
         self.output.write_all(&v.to_le_bytes())?;
         Ok(())
     }
 
     fn serialize_u64(self, v: u64) -> Result<()> {
-        self.field_info.write(0x04, &mut self.output)?;
+        self.write_header(Tag::Long)?;
         self.output.write_all(&v.to_le_bytes())?;
         Ok(())
     }
 
-    fn serialize_f32(self, v: f32) -> Result<()> {
-        self.field_info.write(0x05, &mut self.output)?;
+    fn serialize_f32(self, mut v: f32) -> Result<()> {
+        if self.canonical_zeros && (v == 0.0 || v.is_subnormal()) {
+            v = 0.0;
+        }
+        self.write_header(Tag::Float)?;
         self.output.write_all(&v.to_le_bytes())?;
         Ok(())
     }
 
-    fn serialize_f64(self, v: f64) -> Result<()> {
-        self.field_info.write(0x06, &mut self.output)?;
+    fn serialize_f64(self, mut v: f64) -> Result<()> {
+        if self.canonical_zeros && (v == 0.0 || v.is_subnormal()) {
+            v = 0.0;
+        }
+        self.write_header(Tag::Double)?;
         self.output.write_all(&v.to_le_bytes())?;
         Ok(())
     }
 
+    /// The slice goes straight to the writer after its header, without being copied first, so
+    /// large payloads such as a `bytes::Bytes` with the `bytes` feature stream through as-is.
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        self.field_info.write(0x07, &mut self.output)?;
-        let len = i32::try_from(v.len()).map_err(|_| Error::SeqLen(v.len()))?;
+        let len = i32::try_from(v.len()).map_err(|_| Error::BytesLen(v.len()))?;
+        self.write_header(Tag::ByteArray)?;
         self.output.write_all(&len.to_le_bytes())?;
         self.output.write_all(v)?;
         Ok(())
     }
 
     fn serialize_str(self, v: &str) -> Result<()> {
-        self.field_info.write(0x08, &mut self.output)?;
-        let len = u16::try_from(v.len()).map_err(|_| Error::StrLen(v.len()))?;
-        self.output.write_all(&len.to_le_bytes())?;
-        let mutf8 = mutf8::utf8_to_mutf8(v.as_bytes())?;
-        self.output.write_all(&mutf8)?;
+        self.write_header(Tag::String)?;
+        if !v.bytes().any(|b| b == 0 || b >= 0xf0) {
+            // MUTF-8 agrees with UTF-8 here, so the string is written as it is.
+            let len = u16::try_from(v.len()).map_err(|_| Error::StrLen(v.len()))?;
+            self.output.write_all(&len.to_le_bytes())?;
+            self.output.write_all(v.as_bytes())?;
+            return Ok(());
+        }
+        // The length counts encoded bytes, which differs from the UTF-8 length for strings with
+        // nulls or characters outside the BMP, so it is filled in once the string is encoded.
+        self.scratch.clear();
+        self.scratch.extend_from_slice(&[0; 2]);
+        encode_mutf8(v, &mut self.scratch);
+        let len = self.scratch.len() - 2;
+        let len = u16::try_from(len).map_err(|_| Error::StrLen(len))?;
+        self.scratch[..2].copy_from_slice(&len.to_le_bytes());
+        self.output.write_all(&self.scratch)?;
         Ok(())
     }
 
+    /// 128-bit integers have no tag of their own and are written as a 16 byte array.
     fn serialize_u128(self, v: u128) -> Result<()> {
-        todo!()
+        self.write_header(Tag::ByteArray)?;
+        self.output.write_all(&16i32.to_le_bytes())?;
+        self.output.write_all(&v.to_le_bytes())?;
+        Ok(())
+    }
+
+    /// Formats the value into the reused scratch buffer instead of a fresh `String`.
+    fn collect_str<T: ?Sized + fmt::Display>(self, value: &T) -> Result<()> {
+        let mut buf = mem::take(&mut self.scratch);
+        buf.clear();
+        let mut formatted = String::from_utf8(buf).expect("an empty buffer is valid UTF-8");
+        let result = match write!(formatted, "{value}") {
+            Ok(()) => self.serialize_str(&formatted),
+            Err(fmt::Error) => Err(ser::Error::custom(
+                "a Display implementation returned an error",
+            )),
+        };
+        self.scratch = formatted.into_bytes();
+        result
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self> {
-        todo!()
+    /// Writes the elements straight to the output when the iterator reports an exact length,
+    /// falling back to buffering them otherwise.
+    fn collect_seq<I>(self, iter: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: Serialize,
+    {
+        let iter = iter.into_iter();
+        match iter.size_hint() {
+            (len, Some(upper)) if len == upper => {
+                let mut seq = self.serialize_seq(Some(len))?;
+                for item in iter {
+                    ser::SerializeSeq::serialize_element(&mut seq, &item)?;
+                }
+                ser::SerializeSeq::end(seq)
+            }
+            _ => self.serialize_buffered_list(iter),
+        }
+    }
+
+    /// Passes the length of the map on when the iterator reports it exactly, as those of
+    /// `HashMap` and `BTreeMap` do.
+    fn collect_map<K, V, I>(self, iter: I) -> Result<()>
+    where
+        K: Serialize,
+        V: Serialize,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let iter = iter.into_iter();
+        let len = match iter.size_hint() {
+            (len, Some(upper)) if len == upper => Some(len),
+            _ => None,
+        };
+        let mut map = self.serialize_map(len)?;
+        for (key, value) in iter {
+            ser::SerializeMap::serialize_entry(&mut map, &key, &value)?;
+        }
+        ser::SerializeMap::end(map)
+    }
+
+    /// Compounds carry no entry count, so maps of unknown length are written as they come
+    /// without any buffering unless their keys are being sorted. Each key is built in a reused
+    /// string.
+    ///
+    /// Maps of unknown length are checked for repeated keys, which fail with
+    /// [`Error::DuplicateKey`]. This is how serde writes structs with flattened fields, where a
+    /// flattened map may repeat a named field. Maps of known length aren't checked, and neither
+    /// are `HashMap` and `BTreeMap`, whose exact length `collect_map` passes on. Their keys are
+    /// distinct, although two keys that serialize to the same string are still both written.
+    fn serialize_map(self, len: Option<usize>) -> Result<Self> {
+        if self.sort_map_keys {
+            if self.names.is_some() {
+                Err(Error::InternedSort)?
+            }
+            self.sorted_maps.push(Vec::new());
+        }
+        let keys = match len {
+            Some(_) => None,
+            None => Some(self.spare_key_sets.pop().unwrap_or_default()),
+        };
+        self.map_keys.push(keys);
+        self.write_header(Tag::Compound)?;
+        self.depth += 1;
+        Ok(self)
     }
 
     fn serialize_newtype_struct<T: ?Sized + Serialize>(
         self,
-        _name: &'static str,
+        name: &'static str,
         value: &T,
     ) -> Result<()> {
+        self.array = array::array_tags(name);
         value.serialize(self)
     }
 
     fn serialize_newtype_variant<T: ?Sized + Serialize>(
         self,
-        name: &'static str,
-        variant_index: u32,
+        _name: &'static str,
+        _variant_index: u32,
         variant: &'static str,
         value: &T,
     ) -> Result<()> {
-        todo!()
+        self.begin_variant(variant)?;
+        match self.enum_repr {
+            EnumRepr::External => {
+                value.serialize(&mut *self)?;
+                self.output.write_all(&[0])?;
+                Ok(())
+            }
+            EnumRepr::Internal(_) => {
+                self.field_info = FieldInfo::Inline(variant);
+                value.serialize(self)
+            }
+        }
     }
 
+    /// With [`OptionRepr::Omit`], `None` is written by leaving the field out of its compound.
+    /// List elements can't be left out without shifting the others, so `None` in a list is an
+    /// error, while `Some` is written as the bare value everywhere.
     fn serialize_none(self) -> Result<()> {
-        todo!()
+        match (self.option_repr, &self.field_info) {
+            (_, FieldInfo::Inline(variant)) => Err(Error::UntaggableVariant(variant.to_string())),
+            (OptionRepr::Omit, FieldInfo::InSeq) => Err(Error::NoneInList),
+            (OptionRepr::Omit, _) => {
+                self.field_info = FieldInfo::None;
+                Ok(())
+            }
+            (OptionRepr::Compound, _) => {
+                self.write_header(Tag::Compound)?;
+                self.output.write_all(&[0])
+            }
+        }
     }
 
+    /// A sequence of unknown length is held in memory until it ends, since its count comes
+    /// first on the wire. One of known length is written as it goes and must end with exactly
+    /// the declared number of elements.
     fn serialize_seq(self, len: Option<usize>) -> Result<Self> {
-        self.field_info.write(0x09, &mut self.output)?;
-        let len = len.unwrap_or_else(|| todo!());
-        let len = len.try_into().map_err(|_| Error::SeqLen(len))?;
-        self.field_info = FieldInfo::InSeq(Some(len));
+        let array = self.array.take();
+        self.write_header(array.map_or(Tag::List, |(tag, _)| tag))?;
+        let header = match len {
+            Some(len) => {
+                let len: i32 = len.try_into().map_err(|_| Error::SeqLen(len))?;
+                match array {
+                    // Array elements share a fixed tag and the header carries no element tag.
+                    Some(_) => {
+                        self.output.write_all(&len.to_le_bytes())?;
+                        ListHeader::Written(len)
+                    }
+                    None => ListHeader::Pending(len),
+                }
+            }
+            None => {
+                self.output.hold();
+                ListHeader::Held {
+                    array: array.is_some(),
+                }
+            }
+        };
+        self.lists.push(ListState {
+            header,
+            tag: array.map(|(_, element)| element),
+            count: 0,
+        });
         Ok(self)
     }
 
     fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<()> {
-        todo!()
+        match (self.option_repr, &self.field_info) {
+            (OptionRepr::Omit, _) => value.serialize(self),
+            (_, FieldInfo::Inline(variant)) => Err(Error::UntaggableVariant(variant.to_string())),
+            (OptionRepr::Compound, _) => {
+                self.write_header(Tag::Compound)?;
+                self.field_info = FieldInfo::Named("Some".into());
+                self.depth += 1;
+                value.serialize(&mut *self)?;
+                self.depth -= 1;
+                self.output.write_all(&[0])
+            }
+        }
     }
 
-    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self> {
-        self.field_info.write(0x0a, &mut self.output)?;
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        self.write_header(Tag::Compound)?;
+        self.depth += 1;
         Ok(self)
     }
 
     fn serialize_struct_variant(
         self,
         name: &'static str,
-        variant_index: u32,
+        _variant_index: u32,
         variant: &'static str,
         len: usize,
     ) -> Result<Self> {
-        todo!()
+        self.begin_variant(variant)?;
+        self.depth += 1;
+        match self.enum_repr {
+            EnumRepr::External => self.serialize_struct(name, len),
+            EnumRepr::Internal(_) => Ok(self),
+        }
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self> {
-        todo!()
+        match self.tuple_repr {
+            TupleRepr::List => self.serialize_seq(Some(len)),
+            TupleRepr::Compound => {
+                self.tuples.push(0);
+                self.serialize_struct("", len)
+            }
+        }
     }
 
-    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Self> {
-        todo!()
+    /// Tuple structs are written like tuples. One with a single field is a newtype struct to
+    /// serde and is written as that field alone.
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Self> {
+        self.serialize_tuple(len)
     }
 
+    /// An externally tagged tuple variant holds a list of its fields, whatever the
+    /// [`TupleRepr`].
     fn serialize_tuple_variant(
         self,
-        name: &'static str,
-        variant_index: u32,
+        _name: &'static str,
+        _variant_index: u32,
         variant: &'static str,
         len: usize,
     ) -> Result<Self> {
-        todo!()
+        match self.enum_repr {
+            EnumRepr::External => {
+                self.begin_variant(variant)?;
+                self.depth += 1;
+                self.serialize_seq(Some(len))
+            }
+            EnumRepr::Internal(_) => Err(Error::UntaggableVariant(variant.to_string())),
+        }
     }
 
+    /// NBT has no unit tag, so `()` and unit structs are written as an empty compound. Like any
+    /// other value they get a complete header, so a unit field sits in its struct like the rest.
     fn serialize_unit(self) -> Result<()> {
-        todo!()
+        self.write_header(Tag::Compound)?;
+        self.output.write_all(&[0])?;
+        Ok(())
     }
 
-    fn serialize_unit_struct(self, name: &'static str) -> Result<()> {
-        todo!()
+    /// `PhantomData` is a unit struct to serde, so a `PhantomData` field is an empty compound
+    /// too. Leaving it out instead would make it a missing field when read back.
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_unit()
     }
 
     fn serialize_unit_variant(
         self,
-        name: &'static str,
-        variant_index: u32,
+        _name: &'static str,
+        _variant_index: u32,
         variant: &'static str,
     ) -> Result<()> {
-        todo!()
+        match self.enum_repr {
+            EnumRepr::External => self.serialize_str(variant),
+            EnumRepr::Internal(_) => {
+                self.begin_variant(variant)?;
+                self.output.write_all(&[0])?;
+                Ok(())
+            }
+        }
     }
 }
 
@@ -260,15 +1065,55 @@ impl<W: Write> ser::SerializeMap for &mut Serializer<W> {
     type Error = Error;
 
     fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
-        todo!()
+        let mut name = mem::take(&mut self.spare_name);
+        name.clear();
+        key.serialize(KeySerializer(&mut name))?;
+        let keys = self.map_keys.last_mut().expect("map key outside a map");
+        if let Some(keys) = keys {
+            if !keys.insert(name.clone()) {
+                Err(Error::DuplicateKey(name.clone()))?
+            }
+        }
+        self.field_info = FieldInfo::Named(name.into());
+        Ok(())
     }
 
     fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
-        todo!()
+        if !self.sort_map_keys {
+            return value.serialize(&mut **self);
+        }
+        let key = match &self.field_info {
+            FieldInfo::Named(name) => mutf8::utf8_to_mutf8(name.as_bytes())?.into_owned(),
+            _ => unreachable!("map values follow their key"),
+        };
+        self.output.hold();
+        let result = value.serialize(&mut **self);
+        let entry = self.output.take_held();
+        result?;
+        let map = self
+            .sorted_maps
+            .last_mut()
+            .expect("map value outside a map");
+        map.push((key, entry));
+        Ok(())
     }
 
     fn end(self) -> Result<()> {
-        todo!()
+        if let Some(mut keys) = self.map_keys.pop().flatten() {
+            keys.clear();
+            self.spare_key_sets.push(keys);
+        }
+        if self.sort_map_keys {
+            let mut map = self
+                .sorted_maps
+                .pop()
+                .expect("map ended without being started");
+            map.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (_, entry) in map {
+                self.output.write_uncounted(&entry)?;
+            }
+        }
+        ser::SerializeStruct::end(self)
     }
 }
 
@@ -277,11 +1122,36 @@ impl<W: Write> ser::SerializeSeq for &mut Serializer<W> {
     type Error = Error;
 
     fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.field_info = FieldInfo::InSeq;
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
-        Ok(())
+        let list = self.lists.pop().expect("list ended without being started");
+        match list.header {
+            ListHeader::Pending(len) | ListHeader::Written(len) if list.count != len as usize => {
+                Err(Error::SeqLenMismatch(len as usize, list.count))
+            }
+            // No element was written, so the header is still missing.
+            ListHeader::Pending(len) if list.tag.is_none() => {
+                self.output.write_all(&[Tag::End as u8])?;
+                self.output.write_list_len(len)
+            }
+            ListHeader::Pending(_) | ListHeader::Written(_) => Ok(()),
+            ListHeader::Held { array } => {
+                let len = i32::try_from(list.count).map_err(|_| Error::SeqLen(list.count))?;
+                let mut header = Vec::with_capacity(6);
+                if array {
+                    header.extend_from_slice(&len.to_le_bytes());
+                } else {
+                    header.push(list.tag.unwrap_or(Tag::End) as u8);
+                    let (bytes, used) = encode_list_len(len, self.output.compact_lists);
+                    header.extend_from_slice(&bytes[..used]);
+                }
+                self.output.release(&header)
+            }
+            ListHeader::Deferred => unreachable!("deferred lists are ended by their owner"),
+        }
     }
 }
 
@@ -294,11 +1164,13 @@ impl<W: Write> ser::SerializeStruct for &mut Serializer<W> {
         key: &'static str,
         value: &T,
     ) -> Result<()> {
-        self.field_info = FieldInfo::Named(key);
+        self.field_info = FieldInfo::Named(key.into());
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
+        debug_assert!(self.depth > 0, "compound terminated without being started");
+        self.depth -= 1;
         self.output.write_all(&[0])?;
         Ok(())
     }
@@ -313,11 +1185,14 @@ impl<W: Write> ser::SerializeStructVariant for &mut Serializer<W> {
         key: &'static str,
         value: &T,
     ) -> Result<()> {
-        todo!()
+        ser::SerializeStruct::serialize_field(self, key, value)
     }
 
     fn end(self) -> Result<()> {
-        todo!()
+        if let EnumRepr::External = self.enum_repr {
+            ser::SerializeStruct::end(&mut *self)?;
+        }
+        ser::SerializeStruct::end(self)
     }
 }
 
@@ -326,11 +1201,28 @@ impl<W: Write> ser::SerializeTuple for &mut Serializer<W> {
     type Error = Error;
 
     fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
-        todo!()
+        match self.tuple_repr {
+            TupleRepr::List => ser::SerializeSeq::serialize_element(self, value),
+            TupleRepr::Compound => {
+                let index = self
+                    .tuples
+                    .last_mut()
+                    .expect("tuple element outside of a tuple");
+                self.field_info = FieldInfo::Named(index.to_string().into());
+                *index += 1;
+                value.serialize(&mut **self)
+            }
+        }
     }
 
     fn end(self) -> Result<()> {
-        todo!()
+        match self.tuple_repr {
+            TupleRepr::List => ser::SerializeSeq::end(self),
+            TupleRepr::Compound => {
+                self.tuples.pop();
+                ser::SerializeStruct::end(self)
+            }
+        }
     }
 }
 
@@ -339,11 +1231,11 @@ impl<W: Write> ser::SerializeTupleStruct for &mut Serializer<W> {
     type Error = Error;
 
     fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
-        todo!()
+        ser::SerializeTuple::serialize_element(self, value)
     }
 
     fn end(self) -> Result<()> {
-        todo!()
+        ser::SerializeTuple::end(self)
     }
 }
 
@@ -352,10 +1244,187 @@ impl<W: Write> ser::SerializeTupleVariant for &mut Serializer<W> {
     type Error = Error;
 
     fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
-        todo!()
+        ser::SerializeSeq::serialize_element(self, value)
     }
 
     fn end(self) -> Result<()> {
-        todo!()
+        ser::SerializeSeq::end(&mut *self)?;
+        ser::SerializeStruct::end(self)
+    }
+}
+
+/// Turns a map key into the name of a compound field, appending it to the given string. Strings
+/// are used as-is, and integers and unit variants are converted to their textual form.
+struct KeySerializer<'a>(&'a mut String);
+
+impl KeySerializer<'_> {
+    fn display(self, v: impl fmt::Display) -> Result<()> {
+        use fmt::Write as _;
+        write!(self.0, "{v}").expect("writing to a String cannot fail");
+        Ok(())
+    }
+}
+
+impl ser::Serializer for KeySerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<()> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.display(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.display(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.display(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.display(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.display(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.display(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.display(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.display(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.display(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.display(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.display(v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<()> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<()> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.display(v)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<()> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.display(variant)
     }
 }
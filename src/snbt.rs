@@ -0,0 +1,146 @@
+use std::fmt::{self, Write};
+
+use crate::Value;
+
+/// Byte arrays longer than this are truncated in pretty output.
+const MAX_PRETTY_BYTES: usize = 16;
+
+impl Value {
+    /// Renders the value as multi-line SNBT, indenting nested compounds and lists by `indent`
+    /// spaces per level.
+    ///
+    /// Byte arrays longer than 16 bytes are truncated, ending in an ellipsis and their full
+    /// length, so the output is for reading rather than parsing back.
+    pub fn to_snbt_pretty(&self, indent: usize) -> String {
+        let mut out = String::new();
+        Snbt {
+            out: &mut out,
+            indent: Some(indent),
+        }
+        .value(self, 0)
+        .expect("writing to a String cannot fail");
+        out
+    }
+}
+
+/// Formats the value as compact, single-line SNBT.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Snbt {
+            out: f,
+            indent: None,
+        }
+        .value(self, 0)
+    }
+}
+
+struct Snbt<W> {
+    out: W,
+    /// Spaces per nesting level, or `None` for compact output.
+    indent: Option<usize>,
+}
+
+impl<W: Write> Snbt<W> {
+    fn value(&mut self, value: &Value, level: usize) -> fmt::Result {
+        match value {
+            Value::Byte(v) => write!(self.out, "{v}b"),
+            Value::Short(v) => write!(self.out, "{v}s"),
+            Value::Int(v) => write!(self.out, "{v}"),
+            Value::Long(v) => write!(self.out, "{v}L"),
+            Value::Float(v) => write!(self.out, "{v}f"),
+            Value::Double(v) => write!(self.out, "{v}d"),
+            Value::ByteArray(v) => self.byte_array(v),
+            Value::String(v) => self.string(v),
+            Value::List(v) => {
+                if v.is_empty() {
+                    return self.out.write_str("[]");
+                }
+                self.out.write_char('[')?;
+                for (i, element) in v.iter().enumerate() {
+                    if i > 0 {
+                        self.out.write_char(',')?;
+                    }
+                    self.newline(level + 1)?;
+                    self.value(element, level + 1)?;
+                }
+                self.newline(level)?;
+                self.out.write_char(']')
+            }
+            Value::Compound(v) => {
+                if v.is_empty() {
+                    return self.out.write_str("{}");
+                }
+                self.out.write_char('{')?;
+                for (i, (key, value)) in v.iter().enumerate() {
+                    if i > 0 {
+                        self.out.write_char(',')?;
+                    }
+                    self.newline(level + 1)?;
+                    self.key(key)?;
+                    self.out.write_char(':')?;
+                    if self.indent.is_some() {
+                        self.out.write_char(' ')?;
+                    }
+                    self.value(value, level + 1)?;
+                }
+                self.newline(level)?;
+                self.out.write_char('}')
+            }
+        }
+    }
+
+    fn newline(&mut self, level: usize) -> fmt::Result {
+        match self.indent {
+            Some(indent) => write!(self.out, "\n{:1$}", "", indent * level),
+            None => Ok(()),
+        }
+    }
+
+    fn byte_array(&mut self, bytes: &[u8]) -> fmt::Result {
+        let (shown, truncated) = match self.indent {
+            Some(_) if bytes.len() > MAX_PRETTY_BYTES => (&bytes[..MAX_PRETTY_BYTES], true),
+            _ => (bytes, false),
+        };
+        let separator = if self.indent.is_some() { ", " } else { "," };
+        self.out.write_str("[B;")?;
+        for (i, byte) in shown.iter().enumerate() {
+            if i > 0 {
+                self.out.write_str(separator)?;
+            } else if self.indent.is_some() {
+                self.out.write_char(' ')?;
+            }
+            write!(self.out, "{}b", *byte as i8)?;
+        }
+        if truncated {
+            write!(self.out, "{separator}... ({} bytes)", bytes.len())?;
+        }
+        self.out.write_char(']')
+    }
+
+    fn key(&mut self, key: &str) -> fmt::Result {
+        let bare = !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+'));
+        if bare {
+            self.out.write_str(key)
+        } else {
+            self.string(key)
+        }
+    }
+
+    fn string(&mut self, s: &str) -> fmt::Result {
+        self.out.write_char('"')?;
+        for c in s.chars() {
+            match c {
+                '"' | '\\' => write!(self.out, "\\{c}")?,
+                '\n' => self.out.write_str("\\n")?,
+                '\r' => self.out.write_str("\\r")?,
+                '\t' => self.out.write_str("\\t")?,
+                c if c.is_control() => write!(self.out, "\\u{:04x}", c as u32)?,
+                c => self.out.write_char(c)?,
+            }
+        }
+        self.out.write_char('"')
+    }
+}
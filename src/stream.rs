@@ -0,0 +1,106 @@
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures_core::Stream;
+use serde::de::DeserializeOwned;
+
+use crate::error::{Error, Result};
+use crate::{from_slice, Deserializer};
+
+/// Decodes the documents arriving on a stream of byte chunks, as returned by [`from_stream`].
+///
+/// Chunks are gathered until they hold a whole document, which is then decoded and yielded.
+/// Documents may be split across any number of chunks, and one chunk may complete several
+/// documents. A chunk is only pulled from the inner stream when no buffered document is
+/// complete, so a slow consumer holds back the producer instead of letting chunks pile up.
+///
+/// Each new chunk walks the incomplete document buffered so far again to see whether it has
+/// ended, so chunks much smaller than the documents make for quadratic work.
+pub struct DocumentStream<S, T> {
+    chunks: S,
+    buf: Vec<u8>,
+    /// Whether `buf` has changed since it was last found to be incomplete.
+    grown: bool,
+    /// Set once the inner stream has ended or a document has failed to decode.
+    done: bool,
+    document: PhantomData<fn() -> T>,
+}
+
+/// Decodes a stream of documents, each with its own header, from the chunks of `chunks`.
+///
+/// The stream ends cleanly when `chunks` ends between documents. If it ends partway through
+/// one, a final [`Error::Eof`] is yielded. Like [`Deserializer::into_iter`], the stream stops
+/// after the first error.
+pub fn from_stream<S, T>(chunks: S) -> DocumentStream<S, T>
+where
+    S: Stream<Item = Bytes> + Unpin,
+    T: DeserializeOwned,
+{
+    DocumentStream {
+        chunks,
+        buf: Vec::new(),
+        grown: false,
+        done: false,
+        document: PhantomData,
+    }
+}
+
+impl<S, T> DocumentStream<S, T> {
+    /// Decodes the first buffered document if it is complete.
+    fn next_buffered(&mut self) -> Option<Result<T>>
+    where
+        T: DeserializeOwned,
+    {
+        if !self.grown {
+            return None;
+        }
+        let len = match Deserializer::document_len(&self.buf) {
+            Ok(len) => len,
+            Err(Error::Eof | Error::TruncatedHeader(_)) => {
+                self.grown = false;
+                return None;
+            }
+            Err(e) => return Some(Err(e)),
+        };
+        let document = from_slice(&self.buf[..len]);
+        self.buf.drain(..len);
+        self.grown = !self.buf.is_empty();
+        Some(document)
+    }
+}
+
+impl<S, T> Stream for DocumentStream<S, T>
+where
+    S: Stream<Item = Bytes> + Unpin,
+    T: DeserializeOwned,
+{
+    type Item = Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<T>>> {
+        let this = self.get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+            if let Some(document) = this.next_buffered() {
+                this.done = document.is_err();
+                return Poll::Ready(Some(document));
+            }
+            match Pin::new(&mut this.chunks).poll_next(cx) {
+                Poll::Ready(Some(chunk)) => {
+                    this.buf.extend_from_slice(&chunk);
+                    this.grown = !chunk.is_empty();
+                }
+                Poll::Ready(None) => {
+                    this.done = true;
+                    if !this.buf.is_empty() {
+                        return Poll::Ready(Some(Err(Error::Eof)));
+                    }
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
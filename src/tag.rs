@@ -0,0 +1,88 @@
+use std::fmt;
+
+use crate::error::Error;
+
+/// The type tag that precedes every value in a ShadeNBT stream.
+///
+/// Integer tags only record a width, not a signedness: `i32` and `u32` are both written as
+/// [`Tag::Int`] holding the same 32 bits. Reading a value back as the other signedness
+/// reinterprets those bits as two's complement, so `-1i32` reads back as `u32::MAX`, and
+/// [`Value`](crate::Value) always decodes integers as signed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Tag {
+    End = 0x00,
+    Byte = 0x01,
+    Short = 0x02,
+    Int = 0x03,
+    Long = 0x04,
+    Float = 0x05,
+    Double = 0x06,
+    ByteArray = 0x07,
+    String = 0x08,
+    List = 0x09,
+    Compound = 0x0a,
+    IntArray = 0x0b,
+    LongArray = 0x0c,
+}
+
+impl TryFrom<u8> for Tag {
+    type Error = Error;
+
+    fn try_from(tag: u8) -> Result<Self, Error> {
+        Ok(match tag {
+            0x00 => Tag::End,
+            0x01 => Tag::Byte,
+            0x02 => Tag::Short,
+            0x03 => Tag::Int,
+            0x04 => Tag::Long,
+            0x05 => Tag::Float,
+            0x06 => Tag::Double,
+            0x07 => Tag::ByteArray,
+            0x08 => Tag::String,
+            0x09 => Tag::List,
+            0x0a => Tag::Compound,
+            0x0b => Tag::IntArray,
+            0x0c => Tag::LongArray,
+            _ => return Err(Error::UnknownTag(tag)),
+        })
+    }
+}
+
+impl Tag {
+    pub fn name(self) -> &'static str {
+        match self {
+            Tag::End => "End",
+            Tag::Byte => "Byte",
+            Tag::Short => "Short",
+            Tag::Int => "Int",
+            Tag::Long => "Long",
+            Tag::Float => "Float",
+            Tag::Double => "Double",
+            Tag::ByteArray => "ByteArray",
+            Tag::String => "String",
+            Tag::List => "List",
+            Tag::Compound => "Compound",
+            Tag::IntArray => "IntArray",
+            Tag::LongArray => "LongArray",
+        }
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (0x{:02x})", self.name(), *self as u8)
+    }
+}
+
+/// Displays a raw tag byte by name, falling back to `Unknown` for bytes with no assigned tag.
+pub(crate) struct DisplayTag(pub u8);
+
+impl fmt::Display for DisplayTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Tag::try_from(self.0) {
+            Ok(tag) => tag.fmt(f),
+            Err(_) => write!(f, "Unknown (0x{:02x})", self.0),
+        }
+    }
+}
@@ -0,0 +1,611 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{self, Deserialize, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq};
+
+use crate::error::Error;
+use crate::Tag;
+
+/// Any ShadeNBT value, typed by the tag it is stored with.
+///
+/// Integers are signed, following NBT convention; unsigned Rust integers are stored with the tag
+/// of the same width and read back here as their two's complement reinterpretation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<u8>),
+    String(String),
+    List(Vec<Value>),
+    Compound(BTreeMap<String, Value>),
+}
+
+impl Value {
+    pub fn tag(&self) -> Tag {
+        match self {
+            Value::Byte(_) => Tag::Byte,
+            Value::Short(_) => Tag::Short,
+            Value::Int(_) => Tag::Int,
+            Value::Long(_) => Tag::Long,
+            Value::Float(_) => Tag::Float,
+            Value::Double(_) => Tag::Double,
+            Value::ByteArray(_) => Tag::ByteArray,
+            Value::String(_) => Tag::String,
+            Value::List(_) => Tag::List,
+            Value::Compound(_) => Tag::Compound,
+        }
+    }
+
+    /// Starts building a compound.
+    pub fn compound() -> CompoundBuilder {
+        CompoundBuilder::default()
+    }
+
+    /// Builds a list, failing with [`Error::TagMismatch`] if the elements don't all have the
+    /// same tag.
+    pub fn list<T: Into<Value>>(elements: Vec<T>) -> crate::Result<Value> {
+        let list: Vec<Value> = elements.into_iter().map(Into::into).collect();
+        if let Some(first) = list.first() {
+            let expected = first.tag();
+            if let Some(other) = list.iter().find(|v| v.tag() != expected) {
+                Err(Error::TagMismatch(expected, other.tag()))?
+            }
+        }
+        Ok(Value::List(list))
+    }
+
+    /// Deep-merges `other` into `self`, with `other` winning on conflicts.
+    ///
+    /// When both are compounds, each field of `other` is merged into the field of the same key,
+    /// or added if there is none. Anything else, lists included, replaces `self` wholesale, so a
+    /// compound merged onto a scalar or a scalar onto a compound simply takes its place.
+    pub fn merge(&mut self, other: Value) {
+        match (self, other) {
+            (Value::Compound(fields), Value::Compound(other)) => {
+                for (key, value) in other {
+                    match fields.get_mut(&key) {
+                        Some(field) => field.merge(value),
+                        None => {
+                            fields.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (this, other) => *this = other,
+        }
+    }
+
+    /// Looks up a value by a [JSON Pointer](https://www.rfc-editor.org/rfc/rfc6901) such as
+    /// `"/player/pos/1"`, like `serde_json::Value::pointer`.
+    ///
+    /// Each `/`-separated token names a compound field, or indexes a list when written as a
+    /// plain decimal number without a sign or leading zeros. Within a token, `~1` stands for `/`
+    /// and `~0` for `~`. The empty pointer is the value itself. Returns `None` if a field is
+    /// missing, an index is out of range, or the pointer doesn't start with `/`.
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        pointer_tokens(pointer)?.try_fold(self, |value, token| match value {
+            Value::Compound(fields) => fields.get(&*token),
+            Value::List(elements) => elements.get(list_index(&token)?),
+            _ => None,
+        })
+    }
+
+    /// Like [`pointer`](Self::pointer), but returns a mutable reference.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        pointer_tokens(pointer)?.try_fold(self, |value, token| match value {
+            Value::Compound(fields) => fields.get_mut(&*token),
+            Value::List(elements) => elements.get_mut(list_index(&token)?),
+            _ => None,
+        })
+    }
+}
+
+/// Splits a JSON Pointer into its unescaped tokens, or returns `None` if it is malformed.
+fn pointer_tokens(pointer: &str) -> Option<impl Iterator<Item = Cow<'_, str>>> {
+    let rest = match pointer {
+        "" => None,
+        _ => Some(pointer.strip_prefix('/')?),
+    };
+    Some(
+        rest.into_iter()
+            .flat_map(|rest| rest.split('/'))
+            .map(|token| {
+                if token.contains('~') {
+                    Cow::Owned(token.replace("~1", "/").replace("~0", "~"))
+                } else {
+                    Cow::Borrowed(token)
+                }
+            }),
+    )
+}
+
+/// Parses a pointer token as a list index, which must have no sign and no leading zeros.
+fn list_index(token: &str) -> Option<usize> {
+    if token.starts_with('+') || (token.starts_with('0') && token.len() > 1) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// Builds a [`Value::Compound`] one field at a time.
+#[derive(Clone, Debug, Default)]
+pub struct CompoundBuilder {
+    fields: BTreeMap<String, Value>,
+}
+
+impl CompoundBuilder {
+    /// Adds a field, replacing any earlier field with the same key.
+    pub fn insert(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.fields.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn build(self) -> Value {
+        Value::Compound(self.fields)
+    }
+}
+
+impl From<i8> for Value {
+    fn from(v: i8) -> Self {
+        Value::Byte(v)
+    }
+}
+
+impl From<i16> for Value {
+    fn from(v: i16) -> Self {
+        Value::Short(v)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(v: i32) -> Self {
+        Value::Int(v)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::Long(v)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(v: f32) -> Self {
+        Value::Float(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Double(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::String(v.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::String(v)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Byte(v) => serializer.serialize_i8(*v),
+            Value::Short(v) => serializer.serialize_i16(*v),
+            Value::Int(v) => serializer.serialize_i32(*v),
+            Value::Long(v) => serializer.serialize_i64(*v),
+            Value::Float(v) => serializer.serialize_f32(*v),
+            Value::Double(v) => serializer.serialize_f64(*v),
+            Value::ByteArray(v) => serializer.serialize_bytes(v),
+            Value::String(v) => serializer.serialize_str(v),
+            Value::List(v) => {
+                let mut seq = serializer.serialize_seq(Some(v.len()))?;
+                for element in v {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            }
+            Value::Compound(v) => {
+                let mut map = serializer.serialize_map(Some(v.len()))?;
+                for (key, value) in v {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// A `Value` can itself be decoded into a typed value, as if it had been read from a document.
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Byte(v) => visitor.visit_i8(v),
+            Value::Short(v) => visitor.visit_i16(v),
+            Value::Int(v) => visitor.visit_i32(v),
+            Value::Long(v) => visitor.visit_i64(v),
+            Value::Float(v) => visitor.visit_f32(v),
+            Value::Double(v) => visitor.visit_f64(v),
+            Value::ByteArray(v) => visitor.visit_byte_buf(v),
+            Value::String(v) => visitor.visit_string(v),
+            Value::List(v) => {
+                let mut seq = SeqDeserializer::new(v.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::Compound(v) => {
+                let mut map = MapDeserializer::new(v.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    /// A value that is present is always `Some`.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a ShadeNBT value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Byte(v.into()))
+    }
+
+    fn visit_i8<E>(self, v: i8) -> Result<Value, E> {
+        Ok(Value::Byte(v))
+    }
+
+    fn visit_i16<E>(self, v: i16) -> Result<Value, E> {
+        Ok(Value::Short(v))
+    }
+
+    fn visit_i32<E>(self, v: i32) -> Result<Value, E> {
+        Ok(Value::Int(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Long(v))
+    }
+
+    fn visit_u8<E>(self, v: u8) -> Result<Value, E> {
+        Ok(Value::Byte(v as i8))
+    }
+
+    fn visit_u16<E>(self, v: u16) -> Result<Value, E> {
+        Ok(Value::Short(v as i16))
+    }
+
+    fn visit_u32<E>(self, v: u32) -> Result<Value, E> {
+        Ok(Value::Int(v as i32))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::Long(v as i64))
+    }
+
+    fn visit_f32<E>(self, v: f32) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Double(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::ByteArray(v.to_owned()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::ByteArray(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(element) = seq.next_element()? {
+            list.push(element);
+        }
+        Ok(Value::List(list))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut compound = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry()? {
+            compound.insert(key, value);
+        }
+        Ok(Value::Compound(compound))
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+}
+
+/// A [`Value`] that borrows strings and byte arrays from the input wherever it can.
+///
+/// Decoding from a slice borrows every byte array and every string whose MUTF-8 bytes are
+/// already valid UTF-8, which is any string without nulls or characters outside the BMP. The
+/// rest are converted into owned copies, as is everything read from an
+/// [`io::Read`](std::io::Read).
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueRef<'a> {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Cow<'a, [u8]>),
+    String(Cow<'a, str>),
+    List(Vec<ValueRef<'a>>),
+    Compound(BTreeMap<Cow<'a, str>, ValueRef<'a>>),
+}
+
+impl ValueRef<'_> {
+    pub fn tag(&self) -> Tag {
+        match self {
+            ValueRef::Byte(_) => Tag::Byte,
+            ValueRef::Short(_) => Tag::Short,
+            ValueRef::Int(_) => Tag::Int,
+            ValueRef::Long(_) => Tag::Long,
+            ValueRef::Float(_) => Tag::Float,
+            ValueRef::Double(_) => Tag::Double,
+            ValueRef::ByteArray(_) => Tag::ByteArray,
+            ValueRef::String(_) => Tag::String,
+            ValueRef::List(_) => Tag::List,
+            ValueRef::Compound(_) => Tag::Compound,
+        }
+    }
+
+    /// Copies everything still borrowed, detaching the value from the input.
+    pub fn into_owned(self) -> Value {
+        match self {
+            ValueRef::Byte(v) => Value::Byte(v),
+            ValueRef::Short(v) => Value::Short(v),
+            ValueRef::Int(v) => Value::Int(v),
+            ValueRef::Long(v) => Value::Long(v),
+            ValueRef::Float(v) => Value::Float(v),
+            ValueRef::Double(v) => Value::Double(v),
+            ValueRef::ByteArray(v) => Value::ByteArray(v.into_owned()),
+            ValueRef::String(v) => Value::String(v.into_owned()),
+            ValueRef::List(v) => Value::List(v.into_iter().map(ValueRef::into_owned).collect()),
+            ValueRef::Compound(v) => Value::Compound(
+                v.into_iter()
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Serialize for ValueRef<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ValueRef::Byte(v) => serializer.serialize_i8(*v),
+            ValueRef::Short(v) => serializer.serialize_i16(*v),
+            ValueRef::Int(v) => serializer.serialize_i32(*v),
+            ValueRef::Long(v) => serializer.serialize_i64(*v),
+            ValueRef::Float(v) => serializer.serialize_f32(*v),
+            ValueRef::Double(v) => serializer.serialize_f64(*v),
+            ValueRef::ByteArray(v) => serializer.serialize_bytes(v),
+            ValueRef::String(v) => serializer.serialize_str(v),
+            ValueRef::List(v) => {
+                let mut seq = serializer.serialize_seq(Some(v.len()))?;
+                for element in v {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            }
+            ValueRef::Compound(v) => {
+                let mut map = serializer.serialize_map(Some(v.len()))?;
+                for (key, value) in v {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for ValueRef<'a> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueRefVisitor)
+    }
+}
+
+struct ValueRefVisitor;
+
+impl<'de> Visitor<'de> for ValueRefVisitor {
+    type Value = ValueRef<'de>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a ShadeNBT value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Byte(v.into()))
+    }
+
+    fn visit_i8<E>(self, v: i8) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Byte(v))
+    }
+
+    fn visit_i16<E>(self, v: i16) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Short(v))
+    }
+
+    fn visit_i32<E>(self, v: i32) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Int(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Long(v))
+    }
+
+    fn visit_u8<E>(self, v: u8) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Byte(v as i8))
+    }
+
+    fn visit_u16<E>(self, v: u16) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Short(v as i16))
+    }
+
+    fn visit_u32<E>(self, v: u32) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Int(v as i32))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Long(v as i64))
+    }
+
+    fn visit_f32<E>(self, v: f32) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Float(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Double(v))
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::String(Cow::Borrowed(v)))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::String(Cow::Owned(v.to_owned())))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::String(Cow::Owned(v)))
+    }
+
+    fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::ByteArray(Cow::Borrowed(v)))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::ByteArray(Cow::Owned(v.to_owned())))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<ValueRef<'de>, E> {
+        Ok(ValueRef::ByteArray(Cow::Owned(v)))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ValueRef<'de>, A::Error> {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(element) = seq.next_element()? {
+            list.push(element);
+        }
+        Ok(ValueRef::List(list))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ValueRef<'de>, A::Error> {
+        let mut compound = BTreeMap::new();
+        while let Some((Key(key), value)) = map.next_entry()? {
+            compound.insert(key, value);
+        }
+        Ok(ValueRef::Compound(compound))
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<ValueRef<'de>, D::Error> {
+        ValueRef::deserialize(deserializer)
+    }
+}
+
+/// A compound key that borrows from the input when it can, which `Cow<str>` on its own never
+/// does.
+struct Key<'a>(Cow<'a, str>);
+
+impl<'de: 'a, 'a> Deserialize<'de> for Key<'a> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(KeyVisitor)
+    }
+}
+
+struct KeyVisitor;
+
+impl<'de> Visitor<'de> for KeyVisitor {
+    type Value = Key<'de>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a compound key")
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Key<'de>, E> {
+        Ok(Key(Cow::Borrowed(v)))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Key<'de>, E> {
+        Ok(Key(Cow::Owned(v.to_owned())))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Key<'de>, E> {
+        Ok(Key(Cow::Owned(v)))
+    }
+}
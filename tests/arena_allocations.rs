@@ -0,0 +1,74 @@
+//! Counts allocations with a global allocator, which would replace the allocator of every test
+//! in a shared binary, so this lives in a test binary of its own.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use serde_shade_nbt::{from_slice, to_vec, ArenaValue, Value};
+
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+#[test]
+fn arena_values() {
+    let mut entities = Value::compound();
+    for i in 0..1000 {
+        entities = entities.insert(
+            format!("entity{i}"),
+            Value::compound()
+                .insert("id", "minecraft:zombie")
+                .insert("Health", 20.0f32)
+                .insert(
+                    "Pos",
+                    Value::list(vec![i as f64, 64.0, -(i as f64)]).unwrap(),
+                )
+                .insert("Tag", Value::ByteArray(vec![1, 2, 3]))
+                .build(),
+        );
+    }
+    let value = entities.build();
+    let bytes = to_vec(&value).unwrap();
+
+    let (owned, owned_allocations) = allocations(|| from_slice::<Value>(&bytes).unwrap());
+    let arena = bumpalo::Bump::with_capacity(1 << 20);
+    let (borrowed, arena_allocations) =
+        allocations(|| Value::from_slice_in(&bytes, &arena).unwrap());
+    assert_eq!(owned, value);
+    assert_eq!(borrowed.to_value(), value);
+    assert_eq!(
+        borrowed.get("entity7").and_then(|e| e.get("id")),
+        Some(&ArenaValue::String("minecraft:zombie"))
+    );
+    assert!(
+        arena_allocations * 100 < owned_allocations,
+        "{arena_allocations} arena allocations vs {owned_allocations} owned"
+    );
+}